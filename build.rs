@@ -23,6 +23,7 @@ fn main() {
 
     // Opinionated feature set
     builder.flag("-DSQLITE_ENABLE_API_ARMOR=1");
+    builder.flag("-DSQLITE_ENABLE_CARRAY=1");
     builder.flag("-DSQLITE_ENABLE_FTS3=1");
     builder.flag("-DSQLITE_ENABLE_FTS3_PARENTHESIS=1");
     builder.flag("-DSQLITE_ENABLE_FTS4=1");
@@ -34,6 +35,14 @@ fn main() {
     builder.flag("-DSQLITE_ENABLE_UPDATE_DELETE_LIMIT=1");
     builder.flag("-DSQLITE_SOUNDEX=1");
 
+    // Optional features
+    #[cfg(feature = "column-metadata")]
+    builder.flag("-DSQLITE_ENABLE_COLUMN_METADATA=1");
+    #[cfg(feature = "preupdate-hook")]
+    builder.flag("-DSQLITE_ENABLE_PREUPDATE_HOOK=1");
+    #[cfg(feature = "session")]
+    builder.flag("-DSQLITE_ENABLE_SESSION=1");
+
     // Register source files
     builder.include("dist/");
     builder.file("dist/sqlite3.c");
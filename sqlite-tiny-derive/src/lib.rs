@@ -0,0 +1,517 @@
+#![doc = include_str!("../README.md")]
+// Clippy lints
+#![warn(clippy::large_stack_arrays)]
+#![warn(clippy::arithmetic_side_effects)]
+#![warn(clippy::expect_used)]
+#![warn(clippy::unwrap_used)]
+#![warn(clippy::indexing_slicing)]
+#![warn(clippy::panic)]
+#![warn(clippy::todo)]
+#![warn(clippy::unimplemented)]
+#![warn(clippy::unreachable)]
+#![warn(clippy::missing_panics_doc)]
+#![warn(clippy::allow_attributes_without_reason)]
+#![warn(clippy::cognitive_complexity)]
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+use std::fmt::Write;
+use std::iter::Peekable;
+
+/// Derives `sqlite_tiny::api::row::FromRow` for a struct
+///
+/// # Field mapping
+/// Named fields are read from the column with the same name, tuple fields are read from the column with the same index.
+/// This can be customized via the following field attributes:
+/// - `#[sqlite(rename = "name")]`: Reads the field from the column with the given name
+/// - `#[sqlite(index = 0)]`: Reads the field from the column with the given index
+/// - `#[sqlite(default)]`: Uses `Default::default()` if the column does not exist
+/// - `#[sqlite(skip)]`: Does not read the field but uses `Default::default()`
+///
+/// # Note
+/// For generic structs, the required bounds are added for all fields whose type refers to a generic type parameter.
+#[proc_macro_derive(FromRow, attributes(sqlite))]
+pub fn from_row(input: TokenStream) -> TokenStream {
+    Struct::parse(input).map(|struct_| struct_.impl_from_row()).unwrap_or_else(compile_error)
+}
+
+/// Derives `sqlite_tiny::api::query::ToParams` for a struct
+///
+/// # Field mapping
+/// Named fields are bound to all named parameters with the same name (i.e. `:name`, `@name` and `$name`), tuple fields
+/// are bound to the parameter at the field index plus `1`. This can be customized via the following field attributes:
+/// - `#[sqlite(rename = "name")]`: Binds the field to the named parameters with the given name
+/// - `#[sqlite(index = 0)]`: Binds the field to the parameter at the given index plus `1` (i.e. the same 0-based index
+///   as for `FromRow`)
+/// - `#[sqlite(default)]`: Does not bind the field if the statement does not contain the parameter
+/// - `#[sqlite(skip)]`: Does not bind the field
+///
+/// # Note
+/// Fields are cloned before they are bound. For generic structs, the required bounds are added for all fields whose type
+/// refers to a generic type parameter.
+#[proc_macro_derive(ToParams, attributes(sqlite))]
+pub fn to_params(input: TokenStream) -> TokenStream {
+    Struct::parse(input).map(|struct_| struct_.impl_to_params()).unwrap_or_else(compile_error)
+}
+
+/// Creates a `compile_error!` invocation for the given message
+fn compile_error(message: String) -> TokenStream {
+    let message = format!("compile_error!({message:?});");
+    message.parse().unwrap_or_default()
+}
+
+/// How a field is mapped to a column or parameter
+#[derive(Debug)]
+enum Mapping {
+    /// The field is mapped by name
+    Name(String),
+    /// The field is mapped by an explicit index
+    Index(usize),
+    /// The field is mapped by its position within a tuple struct
+    Position(usize),
+}
+
+/// A struct field
+#[derive(Debug)]
+struct Field {
+    /// The field accessor (i.e. the field name or the tuple index)
+    accessor: String,
+    /// The column or parameter mapping
+    mapping: Mapping,
+    /// Whether to use `Default::default()` if the column does not exist
+    default: bool,
+    /// Whether to skip the field
+    skip: bool,
+    /// The field type
+    type_: Vec<TokenTree>,
+}
+impl Field {
+    /// Parses a field from its tokens
+    fn parse(tokens: Vec<TokenTree>, position: usize, named: bool) -> Result<Self, String> {
+        let mut tokens = tokens.into_iter().peekable();
+        let (mut rename, mut index, mut default, mut skip) = (None, None, false, false);
+
+        // Parse the attributes
+        while matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == '#') {
+            let _ = tokens.next();
+            let Some(TokenTree::Group(attribute)) = tokens.next() else {
+                return Err("Invalid field attribute".to_string());
+            };
+
+            // Parse `sqlite(...)`-attributes only
+            let mut attribute = attribute.stream().into_iter();
+            let (Some(TokenTree::Ident(name)), Some(TokenTree::Group(args))) = (attribute.next(), attribute.next())
+            else {
+                continue;
+            };
+            if name.to_string() != "sqlite" {
+                continue;
+            }
+
+            // Parse the arguments
+            for arg in split_commas(args.stream()) {
+                let mut arg = arg.into_iter();
+                let key = arg.next().map(|key| key.to_string()).unwrap_or_default();
+                let value = arg.nth(1).map(|value| value.to_string());
+                match (key.as_str(), value) {
+                    ("rename", Some(value)) => rename = Some(parse_str(&value)?),
+                    ("index", Some(value)) => index = Some(parse_usize(&value)?),
+                    ("default", None) => default = true,
+                    ("skip", None) => skip = true,
+                    _ => return Err(format!("Invalid `sqlite` attribute argument: {key}")),
+                }
+            }
+        }
+
+        // Skip the visibility
+        if matches!(tokens.peek(), Some(TokenTree::Ident(ident)) if ident.to_string() == "pub") {
+            let _ = tokens.next();
+            if matches!(tokens.peek(), Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis) {
+                let _ = tokens.next();
+            }
+        }
+
+        // Get the field accessor and type
+        let accessor = match named {
+            true => match (tokens.next(), tokens.next()) {
+                (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(punct))) if punct.as_char() == ':' => {
+                    ident.to_string()
+                }
+                _ => return Err("Invalid struct field".to_string()),
+            },
+            false => position.to_string(),
+        };
+        let type_: Vec<_> = tokens.collect();
+        if type_.is_empty() {
+            return Err("Invalid struct field".to_string());
+        }
+
+        // Build the mapping
+        let mapping = match (index, rename) {
+            (Some(_), Some(_)) => return Err("`rename` and `index` are mutually exclusive".to_string()),
+            (Some(index), None) => Mapping::Index(index),
+            (None, Some(rename)) => Mapping::Name(rename),
+            // Note: Raw identifiers are mapped without their prefix
+            (None, None) if named => Mapping::Name(accessor.trim_start_matches("r#").to_string()),
+            (None, None) => Mapping::Position(position),
+        };
+        Ok(Self { accessor, mapping, default, skip, type_ })
+    }
+
+    /// The field type as string
+    fn type_string(&self) -> String {
+        TokenStream::from_iter(self.type_.iter().cloned()).to_string()
+    }
+    /// Whether the field type refers to any of the given generic type parameters
+    fn is_generic(&self, type_parameters: &[String]) -> bool {
+        /// Recursively searches the tokens for the type parameters
+        fn contains(tokens: TokenStream, type_parameters: &[String]) -> bool {
+            tokens.into_iter().any(|token| match token {
+                TokenTree::Ident(ident) => type_parameters.contains(&ident.to_string()),
+                TokenTree::Group(group) => contains(group.stream(), type_parameters),
+                _ => false,
+            })
+        }
+        contains(self.type_.iter().cloned().collect(), type_parameters)
+    }
+}
+
+/// A struct definition
+#[derive(Debug)]
+struct Struct {
+    /// The struct name
+    name: String,
+    /// The generic parameters including bounds but without defaults (without the angle brackets)
+    generics: String,
+    /// The generic arguments (without the angle brackets)
+    arguments: String,
+    /// The names of the generic type parameters
+    type_parameters: Vec<String>,
+    /// The where clause if any
+    where_clause: String,
+    /// The fields
+    fields: Vec<Field>,
+    /// Whether the struct has named fields
+    named: bool,
+}
+impl Struct {
+    /// Parses a struct definition from the derive input
+    fn parse(input: TokenStream) -> Result<Self, String> {
+        let mut tokens = input.into_iter().peekable();
+
+        // Skip attributes and visibility until the `struct` keyword
+        loop {
+            match tokens.next() {
+                Some(TokenTree::Ident(ident)) if ident.to_string() == "struct" => break,
+                Some(TokenTree::Ident(ident)) if ["enum", "union"].contains(&ident.to_string().as_str()) => {
+                    return Err("Only structs are supported".to_string())
+                }
+                Some(_) => continue,
+                None => return Err("Missing struct definition".to_string()),
+            }
+        }
+        let Some(TokenTree::Ident(name)) = tokens.next() else {
+            return Err("Missing struct name".to_string());
+        };
+
+        // Parse the generics and the where clause
+        let (generics, arguments, type_parameters) = parse_generics(&mut tokens)?;
+        let mut where_clause = TokenStream::new();
+        let (fields, named) = loop {
+            match tokens.next() {
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => break (group.stream(), true),
+                Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+                    break (group.stream(), false)
+                }
+                Some(TokenTree::Punct(punct)) if punct.as_char() == ';' => break (TokenStream::new(), true),
+                Some(token) => where_clause.extend([token]),
+                None => return Err("Missing struct body".to_string()),
+            }
+        };
+
+        // Tuple structs may have a trailing where clause
+        for token in tokens {
+            match token {
+                TokenTree::Punct(punct) if punct.as_char() == ';' => break,
+                token => where_clause.extend([token]),
+            }
+        }
+
+        // Parse the fields
+        let fields = (split_commas(fields).into_iter().enumerate())
+            .map(|(position, tokens)| Field::parse(tokens, position, named))
+            .collect::<Result<_, _>>()?;
+        let where_clause = where_clause.to_string();
+        Ok(Self { name: name.to_string(), generics, arguments, type_parameters, where_clause, fields, named })
+    }
+
+    /// Creates the `impl ... for ...`-header for the given trait, with the given bounds for all fields whose type refers
+    /// to a generic type parameter
+    fn impl_header<F>(&self, trait_: &str, bounds: F) -> String
+    where
+        F: Fn(&Field, &str) -> String,
+    {
+        let Self { name, generics, arguments, where_clause, .. } = self;
+
+        // Collect the field bounds
+        let predicates: String = (self.fields.iter())
+            .filter(|field| field.is_generic(&self.type_parameters))
+            .map(|field| bounds(field, &field.type_string()))
+            .collect();
+
+        // Extend the where clause
+        // Note: The where clause may or may not have a trailing comma
+        let where_clause = match (where_clause.is_empty(), predicates.is_empty()) {
+            (_, true) => where_clause.clone(),
+            (true, false) => format!("where {predicates}"),
+            (false, false) if where_clause.trim_end().ends_with(',') => format!("{where_clause} {predicates}"),
+            (false, false) => format!("{where_clause}, {predicates}"),
+        };
+        format!("impl<{generics}> {trait_} for {name}<{arguments}> {where_clause}")
+    }
+
+    /// Implements `FromRow`
+    fn impl_from_row(&self) -> TokenStream {
+        // Create the field initializers
+        let mut initializers = String::new();
+        for field in &self.fields {
+            // Create the read expression
+            // Note: The type is always specified explicitly, since the field bounds may otherwise confuse type inference
+            let type_ = field.type_string();
+            let read = match (&field.mapping, field.skip, field.default) {
+                (_, true, _) => "::core::default::Default::default()".to_string(),
+                (Mapping::Name(name), false, false) => format!("row.read_by_name::<{type_}>({name:?})?"),
+                (Mapping::Index(index) | Mapping::Position(index), false, false) => {
+                    format!("row.read::<{type_}>({index})?")
+                }
+                (Mapping::Name(name), false, true) => format!(
+                    "match row.column_index({name:?}) {{
+                        ::core::option::Option::Some(column) => row.read::<{type_}>(column)?,
+                        ::core::option::Option::None => ::core::default::Default::default(),
+                    }}"
+                ),
+                (Mapping::Index(index) | Mapping::Position(index), false, true) => format!(
+                    "match {index} < row.len() {{
+                        true => row.read::<{type_}>({index})?,
+                        false => ::core::default::Default::default(),
+                    }}"
+                ),
+            };
+
+            // Append the initializer
+            let _ = match self.named {
+                true => write!(initializers, "{}: {read},", field.accessor),
+                false => write!(initializers, "{read},"),
+            };
+        }
+
+        // Create the implementation
+        let body = match self.named {
+            true => format!("Self {{ {initializers} }}"),
+            false => format!("Self({initializers})"),
+        };
+        let implementation = format!(
+            "{header} {{
+                fn from_row(
+                    row: &::sqlite_tiny::api::row::Row
+                ) -> ::core::result::Result<Self, ::sqlite_tiny::error::Error> {{
+                    let _ = row;
+                    ::core::result::Result::Ok({body})
+                }}
+            }}",
+            header = self.impl_header("::sqlite_tiny::api::row::FromRow", |field, type_| match field.skip {
+                true => format!("{type_}: ::core::default::Default,"),
+                false => {
+                    let default = match field.default {
+                        true => format!("{type_}: ::core::default::Default,"),
+                        false => String::new(),
+                    };
+                    format!(
+                        "::sqlite_tiny::api::types::SqliteType: ::core::convert::TryInto<{type_}>,
+                        <::sqlite_tiny::api::types::SqliteType as ::core::convert::TryInto<{type_}>>::Error:
+                            ::std::error::Error + ::core::marker::Send + 'static,
+                        {default}"
+                    )
+                }
+            })
+        );
+        implementation.parse().unwrap_or_else(|e| compile_error(format!("Failed to derive `FromRow`: {e}")))
+    }
+
+    /// Implements `ToParams`
+    fn impl_to_params(&self) -> TokenStream {
+        // Create the bind statements
+        let mut statements = String::new();
+        for field in self.fields.iter().filter(|field| !field.skip) {
+            // Note: The type is always specified explicitly, since the field bounds may otherwise confuse type inference
+            let (value, type_) =
+                (format!("::core::clone::Clone::clone(&self.{})", field.accessor), field.type_string());
+            let _ = match (&field.mapping, field.default) {
+                (Mapping::Name(name), false) => {
+                    writeln!(statements, "let query = query.bind_field::<{type_}>({name:?}, {value})?;")
+                }
+                (Mapping::Index(index) | Mapping::Position(index), false) => {
+                    // Note: Parameter indices start with `1`
+                    let index = index.saturating_add(1);
+                    writeln!(statements, "let query = query.bind::<{type_}>({index}, {value})?;")
+                }
+                (Mapping::Name(name), true) => writeln!(
+                    statements,
+                    "let query = match [\":\", \"@\", \"$\"].iter().any(|prefix| {{
+                        query.parameter_index(&::std::format!(\"{{prefix}}{{}}\", {name:?})).is_some()
+                    }}) {{
+                        true => query.bind_field::<{type_}>({name:?}, {value})?,
+                        false => query,
+                    }};"
+                ),
+                (Mapping::Index(index) | Mapping::Position(index), true) => {
+                    // Note: Parameter indices start with `1`
+                    let index = index.saturating_add(1);
+                    writeln!(
+                        statements,
+                        "let query = match {index} <= query.parameter_count() {{
+                            true => query.bind::<{type_}>({index}, {value})?,
+                            false => query,
+                        }};"
+                    )
+                }
+            };
+        }
+
+        // Create the implementation
+        let implementation = format!(
+            "{header} {{
+                fn to_params<'__sqlite_tiny_db>(
+                    &self,
+                    query: ::sqlite_tiny::api::query::Query<'__sqlite_tiny_db>
+                ) -> ::core::result::Result<
+                    ::sqlite_tiny::api::query::Query<'__sqlite_tiny_db>,
+                    ::sqlite_tiny::error::Error
+                > {{
+                    {statements}
+                    ::core::result::Result::Ok(query)
+                }}
+            }}",
+            header = self.impl_header("::sqlite_tiny::api::query::ToParams", |field, type_| match field.skip {
+                true => String::new(),
+                false => format!(
+                    "{type_}: ::core::clone::Clone,
+                    ::sqlite_tiny::api::types::SqliteType: ::core::convert::TryFrom<{type_}>,
+                    <::sqlite_tiny::api::types::SqliteType as ::core::convert::TryFrom<{type_}>>::Error:
+                        ::std::error::Error + ::core::marker::Send + 'static,"
+                ),
+            })
+        );
+        implementation.parse().unwrap_or_else(|e| compile_error(format!("Failed to derive `ToParams`: {e}")))
+    }
+}
+
+/// Parses the generic parameters if any and returns them without defaults, together with the matching generic arguments
+/// and the names of the type parameters
+fn parse_generics<I>(tokens: &mut Peekable<I>) -> Result<(String, String, Vec<String>), String>
+where
+    I: Iterator<Item = TokenTree>,
+{
+    // Check if there are generics
+    if !matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == '<') {
+        return Ok((String::new(), String::new(), Vec::new()));
+    }
+    let _ = tokens.next();
+
+    // Collect the generic parameters until the matching closing bracket
+    let (mut generics, mut depth, mut arrow) = (Vec::new(), 0usize, false);
+    loop {
+        let token = tokens.next().ok_or("Unterminated generics")?;
+        match &token {
+            // Note: `->` in `Fn`-bounds does not close a bracket
+            TokenTree::Punct(punct) if punct.as_char() == '>' && arrow => (),
+            TokenTree::Punct(punct) if punct.as_char() == '>' && depth == 0 => break,
+            TokenTree::Punct(punct) if punct.as_char() == '>' => depth = depth.saturating_sub(1),
+            TokenTree::Punct(punct) if punct.as_char() == '<' => depth = depth.saturating_add(1),
+            _ => (),
+        }
+        arrow =
+            matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '-' && punct.spacing() == Spacing::Joint);
+        generics.push(token);
+    }
+
+    // Derive the generic arguments from the parameters and strip the defaults
+    let (mut parameters, mut arguments, mut type_parameters) = (Vec::new(), Vec::new(), Vec::new());
+    for parameter in split_commas(generics.into_iter().collect()) {
+        // Note: Default values are not allowed within `impl`-blocks
+        let parameter = strip_default(parameter);
+        let argument = match (parameter.first(), parameter.get(1)) {
+            (Some(TokenTree::Punct(punct)), Some(lifetime)) if punct.as_char() == '\'' => format!("'{lifetime}"),
+            (Some(TokenTree::Ident(ident)), Some(name)) if ident.to_string() == "const" => name.to_string(),
+            (Some(TokenTree::Ident(ident)), _) => {
+                type_parameters.push(ident.to_string());
+                ident.to_string()
+            }
+            _ => return Err("Invalid generic parameter".to_string()),
+        };
+        parameters.push(TokenStream::from_iter(parameter).to_string());
+        arguments.push(argument);
+    }
+    Ok((parameters.join(", "), arguments.join(", "), type_parameters))
+}
+
+/// Strips the default value (i.e. `= ...`) from a generic parameter
+fn strip_default(parameter: Vec<TokenTree>) -> Vec<TokenTree> {
+    let (mut stripped, mut depth, mut arrow) = (Vec::new(), 0usize, false);
+    for token in parameter {
+        match &token {
+            // Note: `->` in `Fn`-bounds does not close a bracket, and `=` within brackets belongs to an associated type
+            TokenTree::Punct(punct) if punct.as_char() == '>' && arrow => (),
+            TokenTree::Punct(punct) if punct.as_char() == '>' => depth = depth.saturating_sub(1),
+            TokenTree::Punct(punct) if punct.as_char() == '<' => depth = depth.saturating_add(1),
+            TokenTree::Punct(punct) if punct.as_char() == '=' && depth == 0 => break,
+            _ => (),
+        }
+        arrow =
+            matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '-' && punct.spacing() == Spacing::Joint);
+        stripped.push(token);
+    }
+    stripped
+}
+
+/// Splits the given tokens at all top-level commas (i.e. commas that are not nested within angle brackets)
+fn split_commas(tokens: TokenStream) -> Vec<Vec<TokenTree>> {
+    let (mut segments, mut segment, mut depth, mut arrow) = (Vec::new(), Vec::new(), 0usize, false);
+    for token in tokens {
+        match &token {
+            // Note: `->` in `Fn`-types does not close a bracket
+            TokenTree::Punct(punct) if punct.as_char() == '>' && arrow => (),
+            TokenTree::Punct(punct) if punct.as_char() == '>' => depth = depth.saturating_sub(1),
+            TokenTree::Punct(punct) if punct.as_char() == '<' => depth = depth.saturating_add(1),
+            TokenTree::Punct(punct) if punct.as_char() == ',' && depth == 0 => {
+                segments.push(std::mem::take(&mut segment));
+                continue;
+            }
+            _ => (),
+        }
+        arrow =
+            matches!(&token, TokenTree::Punct(punct) if punct.as_char() == '-' && punct.spacing() == Spacing::Joint);
+        segment.push(token);
+    }
+
+    // Push the last segment if it is not empty (i.e. if there is no trailing comma)
+    if !segment.is_empty() {
+        segments.push(segment);
+    }
+    segments
+}
+
+/// Parses a string literal
+fn parse_str(literal: &str) -> Result<String, String> {
+    literal
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .filter(|literal| !literal.contains('\\'))
+        .map(str::to_string)
+        .ok_or_else(|| format!("Invalid string literal: {literal}"))
+}
+
+/// Parses an integer literal
+fn parse_usize(literal: &str) -> Result<usize, String> {
+    let literal = literal.trim_end_matches("usize").replace('_', "");
+    literal.parse().map_err(|_| format!("Invalid integer literal: {literal}"))
+}
@@ -1,7 +1,8 @@
 //! An SQLite query result
 
 use crate::api::ffiext::{self, PointerMut, PointerMutFlex};
-use crate::api::row::Row;
+use crate::api::query::Query;
+use crate::api::row::{FromRow, Row};
 use crate::error::Error;
 use crate::{err, ffi, Sqlite};
 
@@ -15,9 +16,9 @@ pub struct Answer<'db> {
     /// If we already have a fetched row pending
     pub(in crate::api) has_row: bool,
 }
-impl Answer<'_> {
+impl<'db> Answer<'db> {
     /// Gets the current pending result row or returns an error if there is no row
-    pub fn row(mut self) -> Result<Row<'static>, Error> {
+    pub fn row(mut self) -> Result<Row<'db>, Error> {
         // Try to fetch the next row if necessary
         if !self.has_row {
             // Do a step to get the next row if any
@@ -55,6 +56,102 @@ impl Answer<'_> {
         Ok(Some(row))
     }
 
+    /// Maps all remaining rows using the given closure and returns them as iterator
+    pub fn map_rows<T, F>(self, f: F) -> MapRows<'db, F>
+    where
+        F: FnMut(&Row) -> Result<T, Error>,
+    {
+        MapRows { answer: self, f, done: false }
+    }
+    /// Collects all remaining rows into a vector
+    pub fn collect_rows<T>(self) -> Result<Vec<T>, Error>
+    where
+        T: FromRow,
+    {
+        self.map_rows(T::from_row).collect()
+    }
+
+    /// Resets the underlying statement so that it can be executed again without being re-prepared
+    ///
+    /// # Note
+    /// If `clear_bindings` is `false`, all bound values are retained and only the changed parameters need to be bound
+    /// again; otherwise all parameters are reset to NULL.
+    pub fn reset(self, clear_bindings: bool) -> Query<'db> {
+        // Note: `sqlite3_reset` only repeats the error of the last step if any, which has already been reported
+        unsafe { ffi::sqlite3_reset(self.raw.as_ptr()) };
+        let query = Query { sqlite: self.sqlite, raw: self.raw };
+
+        // Clear bindings if requested
+        match clear_bindings {
+            true => query.clear_bindings(),
+            false => query,
+        }
+    }
+
+    /// The amount of columns in the result set
+    ///
+    /// # Note
+    /// Unlike [`Row::len`], this is available before the first row is fetched
+    #[allow(clippy::missing_panics_doc, reason = "Panic should never occur during normal operation")]
+    pub fn column_count(&self) -> usize {
+        let columns = unsafe { ffi::sqlite3_column_count(self.raw.as_ptr()) };
+        // Note: If the amount of columns is greater than `usize::MAX` or an `core::ffi::c_int` is greater than
+        //  `usize::MAX`, something is super weird here and we want to panic
+        #[allow(clippy::expect_used, reason = "Panic should never occur during normal operation")]
+        usize::try_from(columns).expect("amount of columns is greater than `usize::MAX`")
+    }
+    /// The names of all columns in the result set
+    pub fn column_names(&self) -> Result<Vec<String>, Error> {
+        let columns = unsafe { ffi::sqlite3_column_count(self.raw.as_ptr()) };
+        (0..columns).map(|column| self.column_name(column)).collect()
+    }
+    /// The name of the given column
+    ///
+    /// # Note
+    /// Column indices start with `0`
+    pub fn column_name(&self, column: std::ffi::c_int) -> Result<String, Error> {
+        let name = unsafe { ffi::sqlite3_column_name(self.raw.as_ptr(), column) };
+        unsafe { ffiext::sqlite3_owned_str(name) }?.ok_or_else(|| err!("Invalid column index: {column}"))
+    }
+    /// The declared type of the given column, or `None` if the column is not a table column (e.g. an expression)
+    ///
+    /// # Note
+    /// Column indices start with `0`
+    pub fn column_decltype(&self, column: std::ffi::c_int) -> Result<Option<String>, Error> {
+        let decltype = unsafe { ffi::sqlite3_column_decltype(self.raw.as_ptr(), column) };
+        unsafe { ffiext::sqlite3_owned_str(decltype) }
+    }
+    /// The name of the database the given column originates from, or `None` if the column is not a table column (e.g.
+    /// an expression)
+    ///
+    /// # Note
+    /// Column indices start with `0`
+    #[cfg(feature = "column-metadata")]
+    pub fn column_database_name(&self, column: std::ffi::c_int) -> Result<Option<String>, Error> {
+        let name = unsafe { ffi::sqlite3_column_database_name(self.raw.as_ptr(), column) };
+        unsafe { ffiext::sqlite3_owned_str(name) }
+    }
+    /// The name of the table the given column originates from, or `None` if the column is not a table column (e.g.
+    /// an expression)
+    ///
+    /// # Note
+    /// Column indices start with `0`
+    #[cfg(feature = "column-metadata")]
+    pub fn column_table_name(&self, column: std::ffi::c_int) -> Result<Option<String>, Error> {
+        let name = unsafe { ffi::sqlite3_column_table_name(self.raw.as_ptr(), column) };
+        unsafe { ffiext::sqlite3_owned_str(name) }
+    }
+    /// The name of the table column the given column originates from, or `None` if the column is not a table column
+    /// (e.g. an expression)
+    ///
+    /// # Note
+    /// Column indices start with `0`
+    #[cfg(feature = "column-metadata")]
+    pub fn column_origin_name(&self, column: std::ffi::c_int) -> Result<Option<String>, Error> {
+        let name = unsafe { ffi::sqlite3_column_origin_name(self.raw.as_ptr(), column) };
+        unsafe { ffiext::sqlite3_owned_str(name) }
+    }
+
     /// Advances the underlying statement towards the first or subsequent row
     pub(in crate::api) fn step(&mut self) -> Result<(), Error> {
         // Do a step
@@ -69,3 +166,46 @@ impl Answer<'_> {
         Ok(())
     }
 }
+
+/// An iterator that maps the remaining rows of an [`Answer`] using a closure
+#[derive(Debug)]
+pub struct MapRows<'db, F> {
+    /// The underlying answer
+    answer: Answer<'db>,
+    /// The mapping closure
+    f: F,
+    /// Whether all rows have been fetched or an error occurred
+    done: bool,
+}
+impl<'db, F> MapRows<'db, F> {
+    /// Returns the underlying answer
+    pub fn into_answer(self) -> Answer<'db> {
+        self.answer
+    }
+}
+impl<T, F> Iterator for MapRows<'_, F>
+where
+    F: FnMut(&Row) -> Result<T, Error>,
+{
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Note: Stepping a finished statement would implicitly reset and re-execute it
+        if self.done {
+            return None;
+        }
+
+        // Fetch and map the next row
+        match self.answer.next_row() {
+            Ok(Some(row)) => Some((self.f)(&row)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
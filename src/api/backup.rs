@@ -0,0 +1,206 @@
+//! Online backups of SQLite databases
+
+use crate::api::ffiext;
+use crate::error::Error;
+use crate::{ffi, Sqlite};
+use std::ffi::c_int;
+use std::mem::ManuallyDrop;
+use std::thread;
+use std::time::Duration;
+
+/// A database that takes part in a backup
+#[derive(Debug, Clone, Copy)]
+pub enum BackupDatabase<'a> {
+    /// An open database
+    Open(&'a Sqlite),
+    /// The path of a database which is opened for the backup
+    ///
+    /// # Note
+    /// A destination path is created if necessary, whereas a source path is opened read-only and must exist.
+    Path(&'a str),
+}
+impl BackupDatabase<'_> {
+    /// Calls the given callback with the backup source, and opens the database read-only if necessary
+    ///
+    /// # Note
+    /// A source path is never created, so a missing database file is an error instead of an empty backup.
+    pub(in crate::api) fn with_source<F, T>(self, callback: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Sqlite) -> Result<T, Error>,
+    {
+        self.with(ffi::SQLITE_OPEN_READONLY, callback)
+    }
+    /// Calls the given callback with the backup destination, and opens or creates the database if necessary
+    pub(in crate::api) fn with_destination<F, T>(self, callback: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Sqlite) -> Result<T, Error>,
+    {
+        self.with(ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE, callback)
+    }
+
+    /// Calls the given callback with the database, and opens the database with the given flags if necessary
+    fn with<F, T>(self, flags: c_int, callback: F) -> Result<T, Error>
+    where
+        F: FnOnce(&Sqlite) -> Result<T, Error>,
+    {
+        match self {
+            Self::Open(sqlite) => callback(sqlite),
+            Self::Path(path) => callback(&Sqlite::raw(path, flags)?),
+        }
+    }
+}
+impl<'a> From<&'a Sqlite> for BackupDatabase<'a> {
+    fn from(sqlite: &'a Sqlite) -> Self {
+        Self::Open(sqlite)
+    }
+}
+impl<'a> From<&'a str> for BackupDatabase<'a> {
+    fn from(path: &'a str) -> Self {
+        Self::Path(path)
+    }
+}
+
+/// The policy to retry backup steps if the source or destination database is busy or locked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RetryPolicy {
+    /// The maximum amount of retries per step
+    pub max_retries: u32,
+    /// The delay between two retries
+    pub delay: Duration,
+}
+impl RetryPolicy {
+    /// A policy that never retries
+    pub const fn never() -> Self {
+        Self { max_retries: 0, delay: Duration::ZERO }
+    }
+}
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { max_retries: 100, delay: Duration::from_millis(10) }
+    }
+}
+
+/// The progress of a backup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Progress {
+    /// The amount of pages that still need to be copied
+    pub remaining: usize,
+    /// The total amount of pages of the source database
+    pub page_count: usize,
+}
+
+/// An online backup from one database into another
+///
+/// # Note
+/// The backup copies the `main` schema page by page. If the source database is modified by another connection between
+/// two steps, the backup restarts automatically; modifications through the source connection itself are applied to the
+/// destination directly.
+#[derive(Debug)]
+pub struct Backup<'db> {
+    /// The source database
+    #[allow(dead_code, reason = "The source must outlive the backup")]
+    source: &'db Sqlite,
+    /// The destination database
+    destination: &'db Sqlite,
+    /// The backup handle
+    raw: *mut ffi::sqlite3_backup,
+    /// The retry policy
+    retry_policy: RetryPolicy,
+}
+impl<'db> Backup<'db> {
+    /// Starts a new backup from `source` into `destination`
+    pub(in crate::api) fn new(source: &'db Sqlite, destination: &'db Sqlite) -> Result<Self, Error> {
+        // Initialize the backup
+        let raw = unsafe {
+            ffi::sqlite3_backup_init(destination.raw.as_ptr(), c"main".as_ptr(), source.raw.as_ptr(), c"main".as_ptr())
+        };
+
+        // Note: If the backup cannot be started, the error is stored in the destination database
+        let false = raw.is_null() else {
+            let retval = unsafe { ffi::sqlite3_errcode(destination.raw.as_ptr()) };
+            return Err(unsafe { ffiext::sqlite3_last_error(retval, destination.raw.as_ptr()) });
+        };
+        Ok(Self { source, destination, raw, retry_policy: RetryPolicy::default() })
+    }
+
+    /// Sets the retry policy for busy or locked steps (defaults to [`RetryPolicy::default`])
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Copies up to `pages` pages (or all remaining pages if `pages` is negative) and returns whether the backup is
+    /// complete or not
+    ///
+    /// # Note
+    /// If the source or destination database is busy or locked, the step is retried according to the retry policy.
+    pub fn step(&mut self, pages: c_int) -> Result<bool, Error> {
+        let mut retries = 0u32;
+        loop {
+            // Perform the step
+            let retval = unsafe { ffi::sqlite3_backup_step(self.raw, pages) };
+            match retval & 0xff {
+                ffi::SQLITE_OK => return Ok(false),
+                ffi::SQLITE_DONE => return Ok(true),
+                ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED if retries < self.retry_policy.max_retries => {
+                    // Wait and retry
+                    retries = retries.saturating_add(1);
+                    thread::sleep(self.retry_policy.delay);
+                }
+                // Note: Backup errors are stored in the destination database
+                _ => return Err(unsafe { ffiext::sqlite3_last_error(retval, self.destination.raw.as_ptr()) }),
+            }
+        }
+    }
+    /// Runs the backup to completion in steps of `pages` pages and calls `progress` after each step
+    pub fn run<F>(&mut self, pages: c_int, mut progress: F) -> Result<(), Error>
+    where
+        F: FnMut(Progress),
+    {
+        loop {
+            let done = self.step(pages)?;
+            progress(self.progress());
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The progress of the backup as of the last step
+    pub fn progress(&self) -> Progress {
+        let remaining = unsafe { ffi::sqlite3_backup_remaining(self.raw) };
+        let page_count = unsafe { ffi::sqlite3_backup_pagecount(self.raw) };
+        Progress {
+            remaining: usize::try_from(remaining).unwrap_or(0),
+            page_count: usize::try_from(page_count).unwrap_or(0),
+        }
+    }
+
+    /// Finishes the backup and releases all associated resources
+    ///
+    /// # Note
+    /// If the backup is finished before it is complete, the destination is left unchanged (or partially updated if it
+    /// is not in a transaction). This is also done implicitly on drop, but ignores any errors.
+    pub fn finish(self) -> Result<(), Error> {
+        // Note: The fields are references and a raw pointer, so we don't need to drop them
+        let this = ManuallyDrop::new(self);
+        let retval = unsafe { ffi::sqlite3_backup_finish(this.raw) };
+        unsafe { ffiext::sqlite3_check_result(retval, this.destination.raw.as_ptr()) }
+    }
+}
+impl Drop for Backup<'_> {
+    fn drop(&mut self) {
+        // Note: `sqlite3_backup_finish` only repeats the error of the last step if any
+        unsafe { ffi::sqlite3_backup_finish(self.raw) };
+    }
+}
+
+/// Performs a complete backup from `source` into `destination`
+pub(in crate::api) fn backup<F>(source: &Sqlite, destination: &Sqlite, pages: c_int, progress: F) -> Result<(), Error>
+where
+    F: FnMut(Progress),
+{
+    let mut backup = Backup::new(source, destination)?;
+    backup.run(pages, progress)?;
+    backup.finish()
+}
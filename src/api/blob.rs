@@ -0,0 +1,160 @@
+//! Incremental I/O on BLOB values
+
+use crate::api::ffiext::{self, PointerMut};
+use crate::error::Error;
+use crate::{err, ffi, Sqlite};
+use std::ffi::{c_int, c_void, CString};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+/// A handle to read and write a single BLOB value incrementally
+///
+/// # Note
+/// The size of the BLOB cannot be changed via the handle, so writes beyond the end of the BLOB are truncated; use
+/// [`crate::api::query::Query::bind_zeroblob`] to preallocate the BLOB first. If the row is modified or deleted while
+/// the handle is open, the handle expires and all further reads and writes fail.
+#[derive(Debug)]
+pub struct Blob<'db> {
+    /// The database
+    sqlite: &'db Sqlite,
+    /// The BLOB handle
+    raw: PointerMut<ffi::sqlite3_blob>,
+    /// The current position within the BLOB
+    position: usize,
+}
+impl<'db> Blob<'db> {
+    /// Opens the BLOB in the given column and row
+    pub(in crate::api) fn open(
+        sqlite: &'db Sqlite,
+        database: &str,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        writable: bool,
+    ) -> Result<Self, Error> {
+        // Prepare the names
+        let database = CString::new(database).map_err(|e| err!(with: e, "Invalid database name"))?;
+        let table = CString::new(table).map_err(|e| err!(with: e, "Invalid table name"))?;
+        let column = CString::new(column).map_err(|e| err!(with: e, "Invalid column name"))?;
+
+        // Open the BLOB
+        let mut blob = std::ptr::null_mut();
+        let retval = unsafe {
+            ffi::sqlite3_blob_open(
+                sqlite.raw.as_ptr(),
+                database.as_ptr(),
+                table.as_ptr(),
+                column.as_ptr(),
+                rowid,
+                c_int::from(writable),
+                &mut blob,
+            )
+        };
+        unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }?;
+
+        // Init self
+        let raw = PointerMut::new(blob, ffi::sqlite3_blob_close);
+        Ok(Self { sqlite, raw, position: 0 })
+    }
+
+    /// Moves the handle to the BLOB in the same column of another row and rewinds it
+    pub fn reopen(&mut self, rowid: i64) -> Result<(), Error> {
+        let retval = unsafe { ffi::sqlite3_blob_reopen(self.raw.as_ptr(), rowid) };
+        unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }?;
+        self.position = 0;
+        Ok(())
+    }
+
+    /// The size of the BLOB in bytes
+    pub fn len(&self) -> usize {
+        let len = unsafe { ffi::sqlite3_blob_bytes(self.raw.as_ptr()) };
+        // Note: SQLite returns `0` for expired handles, and never returns a negative size
+        usize::try_from(len).unwrap_or(0)
+    }
+    /// Whether the BLOB is empty or not
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads exactly `buf.len()` bytes at the given offset, independent of the current position
+    pub fn read_at(&self, buf: &mut [u8], offset: usize) -> Result<(), Error> {
+        let (len, offset) = Self::check_range(buf.len(), offset)?;
+        let retval = unsafe { ffi::sqlite3_blob_read(self.raw.as_ptr(), buf.as_mut_ptr() as *mut c_void, len, offset) };
+        unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }
+    }
+    /// Writes exactly `buf.len()` bytes at the given offset, independent of the current position
+    ///
+    /// # Note
+    /// This fails if the handle is read-only or if the range exceeds the size of the BLOB.
+    pub fn write_at(&mut self, buf: &[u8], offset: usize) -> Result<(), Error> {
+        let (len, offset) = Self::check_range(buf.len(), offset)?;
+        let retval = unsafe { ffi::sqlite3_blob_write(self.raw.as_ptr(), buf.as_ptr() as *const c_void, len, offset) };
+        unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }
+    }
+
+    /// Converts a length and an offset into SQLite integers
+    fn check_range(len: usize, offset: usize) -> Result<(c_int, c_int), Error> {
+        let len = c_int::try_from(len).map_err(|e| err!(with: e, "BLOB range is too large"))?;
+        let offset = c_int::try_from(offset).map_err(|e| err!(with: e, "BLOB offset is too large"))?;
+        Ok((len, offset))
+    }
+    /// The amount of bytes that can be read or written at the current position, capped to `len`
+    fn available(&self, len: usize) -> usize {
+        self.len().saturating_sub(self.position).min(len)
+    }
+}
+impl Read for Blob<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Read the available bytes
+        // Note: `len` never exceeds the buffer size
+        let len = self.available(buf.len());
+        if len == 0 {
+            // Note: SQLite rejects reads at offsets past the end, so we report EOF directly
+            return Ok(0);
+        }
+        let buf = buf.get_mut(..len).unwrap_or_default();
+        self.read_at(buf, self.position).map_err(io_error)?;
+        self.position = self.position.saturating_add(len);
+        Ok(len)
+    }
+}
+impl Write for Blob<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Write the available bytes
+        // Note: If the end of the BLOB has been reached, we return `0`, which `write_all` reports as `WriteZero` error
+        let len = self.available(buf.len());
+        if len == 0 {
+            // Note: SQLite rejects writes at offsets past the end, so we report the end directly
+            return Ok(0);
+        }
+        let buf = buf.get(..len).unwrap_or_default();
+        self.write_at(buf, self.position).map_err(io_error)?;
+        self.position = self.position.saturating_add(len);
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        // Note: Writes go directly to the database page cache
+        Ok(())
+    }
+}
+impl Seek for Blob<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // Compute the new position
+        let position = match pos {
+            SeekFrom::Start(offset) => i128::from(offset),
+            SeekFrom::End(offset) => (self.len() as i128).saturating_add(i128::from(offset)),
+            SeekFrom::Current(offset) => (self.position as i128).saturating_add(i128::from(offset)),
+        };
+
+        // Validate and set the new position
+        // Note: Like for files, seeking beyond the end is allowed, but seeking before the start is an error
+        let position = usize::try_from(position).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        self.position = position;
+        Ok(position as u64)
+    }
+}
+
+/// Converts an error into an I/O error
+fn io_error(error: Error) -> io::Error {
+    // Note: `io::Error` requires a `Sync` error, so we only keep the error description
+    io::Error::other(error.error)
+}
@@ -0,0 +1,102 @@
+//! A least-recently-used cache for prepared statements
+
+use crate::api::ffiext::PointerMut;
+use crate::ffi;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The default capacity of the statement cache
+///
+/// # Note
+/// Applications usually run a fixed set of a few hundred distinct queries, which should all fit into the cache to
+/// avoid thrashing. A cached statement only takes a few kilobytes, so the cache stays small.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// A least-recently-used cache for prepared statements, keyed by their SQL text
+#[derive(Debug)]
+pub struct StatementCache {
+    /// The maximum amount of cached statements
+    capacity: usize,
+    /// The cache generation, which is incremented on flush to discard statements that are currently in use
+    generation: u64,
+    /// A monotonic counter that records the last use of a statement
+    tick: u64,
+    /// The cached statements together with their last use
+    statements: HashMap<String, (u64, PointerMut<ffi::sqlite3_stmt>)>,
+    /// The SQL texts of the cached statements, ordered from least to most recently used
+    order: BTreeMap<u64, String>,
+}
+impl StatementCache {
+    /// Creates a new shared statement cache with the given capacity
+    pub fn new(capacity: usize) -> Arc<Mutex<Self>> {
+        let cache = Self { capacity, generation: 0, tick: 0, statements: HashMap::new(), order: BTreeMap::new() };
+        Arc::new(Mutex::new(cache))
+    }
+
+    /// Locks the given shared cache
+    pub fn lock(cache: &Mutex<Self>) -> MutexGuard<'_, Self> {
+        // Note: The cache is always in a consistent state, so we can safely ignore poisoning
+        cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The current cache generation
+    pub const fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Takes the statement for the given SQL text out of the cache if any
+    pub fn take(&mut self, sql: &str) -> Option<PointerMut<ffi::sqlite3_stmt>> {
+        let (tick, statement) = self.statements.remove(sql)?;
+        self.order.remove(&tick);
+        Some(statement)
+    }
+
+    /// Puts a statement back into the cache and evicts the least recently used statements if necessary
+    ///
+    /// # Note
+    /// The statement is discarded if the cache has been flushed since the statement has been taken out of the cache, or
+    /// if the cache already contains a statement with the same SQL text.
+    pub fn put(&mut self, sql: String, statement: PointerMut<ffi::sqlite3_stmt>, generation: u64) {
+        // Check if the statement is outdated or duplicate
+        let is_current = generation == self.generation;
+        if !is_current || self.statements.contains_key(&sql) {
+            return;
+        }
+
+        // Insert the statement and evict the least recently used ones
+        self.tick = self.tick.wrapping_add(1);
+        self.order.insert(self.tick, sql.clone());
+        self.statements.insert(sql, (self.tick, statement));
+        self.evict();
+    }
+
+    /// Sets the capacity and evicts the least recently used statements if necessary
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Discards all cached statements, including those which are currently in use
+    pub fn flush(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.statements.clear();
+        self.order.clear();
+    }
+
+    /// Evicts the least recently used statements until the cache does not exceed its capacity
+    fn evict(&mut self) {
+        while self.statements.len() > self.capacity {
+            let Some((_, sql)) = self.order.pop_first() else {
+                return;
+            };
+            self.statements.remove(&sql);
+        }
+    }
+}
+unsafe impl Send for StatementCache {
+    // This struct is safely send because:
+    //  - the underlying statements belong to a database which is opened with `SQLITE_OPEN_FULLMUTEX`, so SQLite
+    //    serializes all accesses to them
+    //  - the cached statements are not in use and exclusively owned by the cache, so they can only be accessed through
+    //    the cache
+}
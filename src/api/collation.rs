@@ -0,0 +1,146 @@
+//! Application-defined collations
+
+use crate::api::ffiext;
+use crate::api::function::drop_boxed;
+use crate::api::hooks::{self, Hooks};
+use crate::error::Error;
+use crate::{err, ffi, Sqlite};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// A boxed collation comparator
+pub type Collation = Box<dyn Fn(&str, &str) -> Ordering + Send>;
+
+/// Registers a collation on the given raw database handle
+///
+/// # Safety
+/// `database` must be a valid database handle.
+unsafe fn create_raw<F>(database: *mut ffi::sqlite3, name: &CStr, collation: F) -> Result<(), Error>
+where
+    F: Fn(&str, &str) -> Ordering + Send + 'static,
+{
+    // Register the collation
+    let collation = Box::into_raw(Box::new(collation));
+    let retval = unsafe {
+        ffi::sqlite3_create_collation_v2(
+            database,
+            name.as_ptr(),
+            ffi::SQLITE_UTF8,
+            collation as *mut c_void,
+            Some(compare::<F>),
+            Some(drop_boxed::<F>),
+        )
+    };
+
+    // Note: Unlike for functions, SQLite does not call the destructor if the registration fails
+    if retval != ffi::SQLITE_OK {
+        unsafe { drop_boxed::<F>(collation as *mut c_void) };
+    }
+    unsafe { ffiext::sqlite3_check_result(retval, database) }
+}
+
+/// Registers a collation
+pub(in crate::api) fn create<F>(sqlite: &Sqlite, name: &str, collation: F) -> Result<(), Error>
+where
+    F: Fn(&str, &str) -> Ordering + Send + 'static,
+{
+    let name = CString::new(name).map_err(|e| err!(with: e, "Invalid collation name"))?;
+    unsafe { create_raw(sqlite.raw.as_ptr(), &name, collation) }
+}
+
+/// Registers a callback that is called if an unknown collation is needed
+pub(in crate::api) fn on_needed<F>(sqlite: &Sqlite, callback: F) -> Result<(), Error>
+where
+    F: Fn(&str) -> Option<Collation> + Send + 'static,
+{
+    // Register the callback, keep it alive and drop the previous one
+    // Note: SQLite does not free the application data of this callback
+    let (data, callback) = hooks::share(callback);
+    Hooks::register(
+        sqlite,
+        |hooks| &mut hooks.collation_needed,
+        Some(callback),
+        || {
+            let retval = unsafe { ffi::sqlite3_collation_needed(sqlite.raw.as_ptr(), data, Some(call_needed::<F>)) };
+            unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+        },
+    )
+}
+
+/// Removes the callback that is called if an unknown collation is needed
+pub(in crate::api) fn remove_needed(sqlite: &Sqlite) -> Result<(), Error> {
+    // Unregister the callback and drop it
+    Hooks::register(
+        sqlite,
+        |hooks| &mut hooks.collation_needed,
+        None,
+        || {
+            let retval = unsafe { ffi::sqlite3_collation_needed(sqlite.raw.as_ptr(), ptr::null_mut(), None) };
+            unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+        },
+    )
+}
+
+/// Converts a string from SQLite into a Rust string
+///
+/// # Safety
+/// `chars` must point to `len` valid bytes.
+unsafe fn to_str<'a>(len: c_int, chars: *const c_void) -> Cow<'a, str> {
+    let false = chars.is_null() else {
+        // SQLite may pass `NULL` for empty strings
+        return Cow::Borrowed("");
+    };
+
+    // Note: SQLite may pass invalid UTF-8 if a BLOB is cast to TEXT, so we fall back to a lossy conversion
+    let bytes = unsafe { std::slice::from_raw_parts(chars as *const u8, len as usize) };
+    String::from_utf8_lossy(bytes)
+}
+
+/// Compares two strings
+unsafe extern "C" fn compare<F>(
+    data: *mut c_void,
+    len_a: c_int,
+    chars_a: *const c_void,
+    len_b: c_int,
+    chars_b: *const c_void,
+) -> c_int
+where
+    F: Fn(&str, &str) -> Ordering,
+{
+    let ordering = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a boxed `F` which lives until the destructor is called
+        let collation = unsafe { &*(data as *const F) };
+        let (a, b) = unsafe { (to_str(len_a, chars_a), to_str(len_b, chars_b)) };
+        collation(&a, &b)
+    }));
+
+    // Note: Collations cannot fail, so we treat a panic as equality
+    match ordering.unwrap_or(Ordering::Equal) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+/// Registers a needed collation
+unsafe extern "C" fn call_needed<F>(data: *mut c_void, database: *mut ffi::sqlite3, _: c_int, name: *const c_char)
+where
+    F: Fn(&str) -> Option<Collation>,
+{
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a shared `F` which lives until it is replaced or the database is closed
+        let callback = unsafe { hooks::retain::<F>(data) };
+        let name = unsafe { CStr::from_ptr(name) };
+        let Some(collation) = name.to_str().ok().and_then(|name| callback(name)) else {
+            // Note: If no collation is registered, SQLite fails with an appropriate error
+            return;
+        };
+
+        // Register the collation
+        // Note: If the registration fails, SQLite fails with an appropriate error
+        let _ = unsafe { create_raw(database, name, collation) };
+    }));
+}
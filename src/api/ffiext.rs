@@ -1,7 +1,10 @@
 //! FFI types and helpers
 
-use crate::ffi;
-use std::ffi::c_int;
+use crate::api::types::{SqliteType, ValueRef};
+use crate::error::Error;
+use crate::{err, ffi};
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::fmt::{self, Debug, Formatter};
 
 /// Gets the last error from the database as [`crate::error::Error`]
 ///
@@ -11,7 +14,6 @@ use std::ffi::c_int;
 #[doc(hidden)]
 pub unsafe fn sqlite3_last_error(retval: i32, database: *mut ffi::sqlite3) -> crate::error::Error {
     use std::borrow::Cow;
-    use std::ffi::CStr;
 
     // Get the error string
     let error = ffi::sqlite3_errstr(retval);
@@ -21,13 +23,24 @@ pub unsafe fn sqlite3_last_error(retval: i32, database: *mut ffi::sqlite3) -> cr
     };
 
     // Append database specific error
+    let (mut extended_code, mut offset) = (retval, None);
     if !database.is_null() {
         // Get error from database
         let error = ffi::sqlite3_errmsg(database);
         let message_ = CStr::from_ptr(error).to_string_lossy();
         message = Cow::Owned(format!("{message} ({message_})"));
+
+        // Prefer the extended result code if it belongs to the given result code
+        let extended_code_ = ffi::sqlite3_extended_errcode(database);
+        if extended_code_ & 0xff == retval & 0xff {
+            extended_code = extended_code_;
+        }
+
+        // Get the error offset if any
+        let offset_ = ffi::sqlite3_error_offset(database);
+        offset = usize::try_from(offset_).ok();
     }
-    crate::err!("SQLite error: {message}")
+    crate::error::Error::sqlite(format!("SQLite error: {message}"), extended_code, offset)
 }
 
 /// Helper to translate a result code into a `Result`
@@ -44,13 +57,199 @@ pub unsafe fn sqlite3_check_result(retval: i32, database: *mut ffi::sqlite3) ->
     }
 }
 
+/// Copies a C string returned by SQLite into an owned Rust string, or returns `None` if the pointer is `NULL`
+///
+/// # Safety
+/// This function operates on a raw C string. If `chars` is not `NULL` but does not point to a valid, `NUL`-terminated
+/// C string, the behaviour is undefined.
+#[doc(hidden)]
+pub unsafe fn sqlite3_owned_str(chars: *const c_char) -> Result<Option<String>, Error> {
+    let false = chars.is_null() else {
+        // There is no string
+        return Ok(None);
+    };
+
+    // Copy the string
+    let chars = CStr::from_ptr(chars);
+    let string = chars.to_str().map_err(|e| err!(with: e, "SQLite string is not valid UTF-8"))?;
+    Ok(Some(string.to_string()))
+}
+
+/// Copies a Rust string into a `NUL`-terminated C string allocated by SQLite, or returns `NULL` if SQLite is out of
+/// memory
+///
+/// # Important
+/// The returned string must be freed with `sqlite3_free`, usually by handing it over to SQLite (e.g. as error message).
+#[doc(hidden)]
+pub fn sqlite3_malloc_str(string: &str) -> *mut c_char {
+    // Allocate the buffer
+    let len = string.len().saturating_add(1);
+    let chars = unsafe { ffi::sqlite3_malloc64(len as u64) } as *mut u8;
+    let false = chars.is_null() else {
+        // SQLite is out of memory
+        return std::ptr::null_mut();
+    };
+
+    // Copy the string and append the `NUL`-terminator
+    // Note: If the string contains a `NUL`-byte, C truncates it there
+    unsafe { std::ptr::copy_nonoverlapping(string.as_ptr(), chars, string.len()) };
+    unsafe { *chars.add(string.len()) = 0 };
+    chars as *mut c_char
+}
+
+/// Gets a borrowed reference to the given SQLite value
+///
+/// # Safety
+/// This function operates on a raw SQLite value. If `value` is invalid, or if the returned reference outlives the value
+/// (i.e. if it is used after the callback that received the value returns), the behaviour is undefined.
+#[doc(hidden)]
+pub unsafe fn sqlite3_value_ref<'a>(value: *mut ffi::sqlite3_value) -> Result<ValueRef<'a>, Error> {
+    // Get the type and read the value as said type
+    let type_ = ffi::sqlite3_value_type(value);
+    match type_ {
+        ffi::SQLITE_NULL => Ok(ValueRef::Null),
+        ffi::SQLITE_INTEGER => Ok(ValueRef::Integer(ffi::sqlite3_value_int64(value))),
+        ffi::SQLITE_FLOAT => Ok(ValueRef::Real(ffi::sqlite3_value_double(value))),
+        ffi::SQLITE_TEXT => {
+            // Get text value
+            let chars = ffi::sqlite3_value_text(value);
+            let false = chars.is_null() else {
+                // SQLite only returns `NULL` for a TEXT value if it is out of memory
+                return Err(err!("Failed to read SQLite string"));
+            };
+
+            // Get rust string
+            let len = ffi::sqlite3_value_bytes(value);
+            let bytes = std::slice::from_raw_parts(chars, len as usize);
+            let text = std::str::from_utf8(bytes).map_err(|e| err!(with: e, "SQLite string is not valid UTF-8"))?;
+            Ok(ValueRef::Text(text))
+        }
+        ffi::SQLITE_BLOB => {
+            // Get blob value
+            let data = ffi::sqlite3_value_blob(value);
+            let false = data.is_null() else {
+                // SQLite has a "special" way of handling empty blobs
+                return Ok(ValueRef::Blob(&[]));
+            };
+
+            // Get blob length
+            let len = ffi::sqlite3_value_bytes(value);
+            Ok(ValueRef::Blob(std::slice::from_raw_parts(data as *const u8, len as usize)))
+        }
+        _ => Err(err!("Unknown SQLite value type: {type_}")),
+    }
+}
+
+/// Gets borrowed references to the given SQLite function arguments
+///
+/// # Safety
+/// This function operates on raw SQLite values. If `argv` does not point to `argc` valid values, or if the returned
+/// references outlive the values, the behaviour is undefined.
+#[doc(hidden)]
+pub unsafe fn sqlite3_value_refs<'a>(
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) -> Result<Vec<ValueRef<'a>>, Error> {
+    let false = argv.is_null() else {
+        // There are no arguments
+        return Ok(Vec::new());
+    };
+
+    // Read all arguments
+    let argv = std::slice::from_raw_parts(argv, argc as usize);
+    argv.iter().map(|value| sqlite3_value_ref(*value)).collect()
+}
+
+/// Sets the result of an SQLite function call
+///
+/// # Safety
+/// This function operates on a raw SQLite function context. If `context` is invalid, the behaviour is undefined.
+#[doc(hidden)]
+pub unsafe fn sqlite3_result(context: *mut ffi::sqlite3_context, result: Result<SqliteType, Error>) {
+    match result {
+        Ok(SqliteType::Null) => ffi::sqlite3_result_null(context),
+        Ok(SqliteType::Integer(value)) => ffi::sqlite3_result_int64(context, value),
+        Ok(SqliteType::Real(value)) => ffi::sqlite3_result_double(context, value),
+        Ok(SqliteType::Text(value)) => {
+            // Set the text value and instruct SQLite to immediately copy the value
+            let (value, len) = (value.as_ptr() as _, value.len() as _);
+            ffi::sqlite3_result_text64(context, value, len, ffi::sqlite3_transient(), ffi::SQLITE_UTF8 as _)
+        }
+        Ok(SqliteType::Blob(value)) => {
+            // Set the blob value and instruct SQLite to immediately copy the value
+            let (value, len) = (value.as_ptr() as _, value.len() as _);
+            ffi::sqlite3_result_blob64(context, value, len, ffi::sqlite3_transient())
+        }
+        Err(error) => {
+            // Set the error message and code
+            // Note: SQLite copies the message immediately
+            let len = c_int::try_from(error.error.len()).unwrap_or(c_int::MAX);
+            ffi::sqlite3_result_error(context, error.error.as_ptr() as _, len);
+            if let Some(code) = error.extended_code {
+                ffi::sqlite3_result_error_code(context, code);
+            }
+        }
+    }
+}
+
+/// Hands the ownership of the given buffer over to SQLite and returns the buffer together with the destructor that
+/// must be passed to SQLite to free it
+///
+/// # Note
+/// SQLite passes only the data pointer to the destructor, so the buffer is prefixed in place with a small header that
+/// records its length and capacity. This shifts the bytes within the allocation (and may grow it once), but avoids a
+/// second copy within SQLite and any global bookkeeping.
+///
+/// # Important
+/// If SQLite does not take ownership of the buffer (i.e. never calls the destructor), the buffer is leaked.
+#[doc(hidden)]
+pub fn sqlite3_handover(mut buffer: Vec<u8>) -> (*const [u8], ffi::sqlite3_destructor_type) {
+    /// The size of the header that precedes the handed-over bytes
+    const HEADER: usize = 2 * size_of::<usize>();
+
+    /// Frees a buffer that has been handed over to SQLite
+    unsafe extern "C" fn destructor(ptr: *mut c_void) {
+        // Read the header and reassemble the buffer
+        // Note: The header is not necessarily aligned
+        let base = unsafe { (ptr as *mut u8).sub(HEADER) };
+        let len = unsafe { (base as *const usize).read_unaligned() };
+        let capacity = unsafe { (base.add(size_of::<usize>()) as *const usize).read_unaligned() };
+        drop(unsafe { Vec::from_raw_parts(base, HEADER.saturating_add(len), capacity) });
+    }
+
+    // Empty buffers don't need to be freed
+    if buffer.is_empty() {
+        // Note: We use a non-empty static to provide a valid address
+        let empty: &'static [u8] = &b"\0"[..0];
+        return (empty, ffi::SQLITE_STATIC);
+    }
+
+    // Prepend the header
+    // Note: The capacity must be taken after inserting the header, since the insertion may grow the allocation
+    let len = buffer.len();
+    buffer.splice(..0, [0; HEADER]);
+    let capacity = buffer.capacity();
+    let (len_bytes, capacity_bytes) = (len.to_ne_bytes(), capacity.to_ne_bytes());
+    let header = len_bytes.iter().chain(capacity_bytes.iter());
+    buffer.iter_mut().zip(header).for_each(|(byte, header)| *byte = *header);
+
+    // Leak the buffer and hand out the bytes after the header
+    let buffer = std::mem::ManuallyDrop::new(buffer);
+    let slice = buffer.get(HEADER..).unwrap_or_default() as *const [u8];
+    (slice, Some(destructor))
+}
+
+/// A callback that takes over ownership of a pointer on drop instead of destroying it
+pub type Recycler<T> = Box<dyn FnOnce(PointerMut<T>)>;
+
 /// An "owned", mutable pointer
-#[derive(Debug)]
 pub struct PointerMut<T> {
     /// The underlying raw pointer
     ptr: *mut T,
     /// An optional callback that is called on drop
     on_drop: unsafe extern "C" fn(*mut T) -> c_int,
+    /// An optional callback that takes over ownership on drop instead of calling `on_drop`
+    recycler: Option<Recycler<T>>,
 }
 impl<T> PointerMut<T> {
     /// Creates a new owned pointer
@@ -59,7 +258,16 @@ impl<T> PointerMut<T> {
     /// This function panics if the given pointer is `NULL`.
     pub fn new(ptr: *mut T, on_drop: unsafe extern "C" fn(*mut T) -> c_int) -> Self {
         assert!(!ptr.is_null(), "cannot create an owned NULL pointer");
-        Self { ptr, on_drop }
+        Self { ptr, on_drop, recycler: None }
+    }
+    /// Sets a recycler which takes over ownership of the pointer on drop instead of destroying it
+    ///
+    /// # Note
+    /// The pointer that is passed to the recycler has no recycler set, so it is destroyed on drop unless a new recycler
+    /// is set.
+    pub fn with_recycler(mut self, recycler: Recycler<T>) -> Self {
+        self.recycler = Some(recycler);
+        self
     }
 
     /// Returns the underlying pointer
@@ -67,8 +275,23 @@ impl<T> PointerMut<T> {
         self.ptr
     }
 }
+impl<T> Debug for PointerMut<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PointerMut")
+            .field("ptr", &self.ptr)
+            .field("on_drop", &self.on_drop)
+            .field("recycler", &self.recycler.as_ref().map(|_| "<recycler>"))
+            .finish()
+    }
+}
 impl<T> Drop for PointerMut<T> {
     fn drop(&mut self) {
+        // Hand over ownership to the recycler if any
+        if let Some(recycler) = self.recycler.take() {
+            let pointer = Self { ptr: self.ptr, on_drop: self.on_drop, recycler: None };
+            return recycler(pointer);
+        }
+
         // Call the on-drop callback
         unsafe { (self.on_drop)(self.ptr) };
     }
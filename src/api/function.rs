@@ -0,0 +1,331 @@
+//! Application-defined SQL functions
+
+use crate::api::ffiext;
+use crate::api::types::{SqliteType, ValueRef};
+use crate::error::Error;
+use crate::{err, ffi, Sqlite};
+use std::ffi::{c_int, c_void, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// A user-defined aggregate function
+///
+/// # Note
+/// For every group, a new state is created via [`Aggregate::init`], updated via [`Aggregate::step`] for every row, and
+/// consumed by [`Aggregate::finalize`]. The state lives in Rust memory and is dropped even if the query is aborted.
+pub trait Aggregate {
+    /// The per-group state
+    type State;
+
+    /// Creates a new state for a group
+    fn init(&self) -> Self::State;
+    /// Updates the state with the arguments of the next row
+    fn step(&self, state: &mut Self::State, args: &[ValueRef]) -> Result<(), Error>;
+    /// Consumes the state and computes the result for the group
+    fn finalize(&self, state: Self::State) -> Result<SqliteType, Error>;
+}
+
+/// A user-defined aggregate window function
+///
+/// # Note
+/// In addition to the [`Aggregate`] callbacks, window functions can remove the oldest row from the state via
+/// [`WindowFunction::inverse`] and compute the current result without consuming the state via
+/// [`WindowFunction::value`].
+pub trait WindowFunction: Aggregate {
+    /// Removes the arguments of the oldest row from the state
+    fn inverse(&self, state: &mut Self::State, args: &[ValueRef]) -> Result<(), Error>;
+    /// Computes the current result for the state
+    fn value(&self, state: &Self::State) -> Result<SqliteType, Error>;
+}
+
+/// Registers a function with the given callbacks and destructor
+///
+/// # Safety
+/// The callbacks must be valid for the given application data, and the destructor must free the application data. The
+/// destructor is called by SQLite even if the registration fails.
+#[allow(clippy::too_many_arguments, reason = "Mirrors `sqlite3_create_function_v2`")]
+pub(in crate::api) unsafe fn create_function(
+    sqlite: &Sqlite,
+    name: &str,
+    n_args: c_int,
+    flags: c_int,
+    data: *mut c_void,
+    x_func: Option<unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value)>,
+    x_step: Option<unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value)>,
+    x_final: Option<unsafe extern "C" fn(*mut ffi::sqlite3_context)>,
+    x_destroy: Option<unsafe extern "C" fn(*mut c_void)>,
+) -> Result<(), Error> {
+    let name = match CString::new(name) {
+        Ok(name) => name,
+        Err(e) => {
+            // Free the application data like SQLite would do
+            if let Some(x_destroy) = x_destroy {
+                x_destroy(data);
+            }
+            return Err(err!(with: e, "Invalid function name"));
+        }
+    };
+
+    // Register the function
+    let flags = flags | ffi::SQLITE_UTF8;
+    let retval = ffi::sqlite3_create_function_v2(
+        sqlite.raw.as_ptr(),
+        name.as_ptr(),
+        n_args,
+        flags,
+        data,
+        x_func,
+        x_step,
+        x_final,
+        x_destroy,
+    );
+    ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr())
+}
+
+/// Registers a scalar function
+pub(in crate::api) fn create_scalar<F>(
+    sqlite: &Sqlite,
+    name: &str,
+    n_args: c_int,
+    flags: c_int,
+    function: F,
+) -> Result<(), Error>
+where
+    F: Fn(&[ValueRef]) -> Result<SqliteType, Error> + Send + 'static,
+{
+    let function = Box::into_raw(Box::new(function));
+    unsafe {
+        // Note: The application data is a boxed `F`
+        create_function(
+            sqlite,
+            name,
+            n_args,
+            flags,
+            function as *mut c_void,
+            Some(call_scalar::<F>),
+            None,
+            None,
+            Some(drop_boxed::<F>),
+        )
+    }
+}
+
+/// Registers an aggregate function
+pub(in crate::api) fn create_aggregate<A>(
+    sqlite: &Sqlite,
+    name: &str,
+    n_args: c_int,
+    flags: c_int,
+    aggregate: A,
+) -> Result<(), Error>
+where
+    A: Aggregate + Send + 'static,
+{
+    let aggregate = Box::into_raw(Box::new(aggregate));
+    unsafe {
+        // Note: The application data is a boxed `A`
+        create_function(
+            sqlite,
+            name,
+            n_args,
+            flags,
+            aggregate as *mut c_void,
+            None,
+            Some(step_aggregate::<A>),
+            Some(final_aggregate::<A>),
+            Some(drop_boxed::<A>),
+        )
+    }
+}
+
+/// Registers an aggregate window function
+pub(in crate::api) fn create_window<W>(
+    sqlite: &Sqlite,
+    name: &str,
+    n_args: c_int,
+    flags: c_int,
+    window: W,
+) -> Result<(), Error>
+where
+    W: WindowFunction + Send + 'static,
+{
+    let name = CString::new(name).map_err(|e| err!(with: e, "Invalid function name"))?;
+    let window = Box::into_raw(Box::new(window));
+
+    // Register the function
+    // Note: The application data is a boxed `W`, and SQLite calls the destructor even if the registration fails
+    let flags = flags | ffi::SQLITE_UTF8;
+    let retval = unsafe {
+        ffi::sqlite3_create_window_function(
+            sqlite.raw.as_ptr(),
+            name.as_ptr(),
+            n_args,
+            flags,
+            window as *mut c_void,
+            Some(step_aggregate::<W>),
+            Some(final_aggregate::<W>),
+            Some(value_window::<W>),
+            Some(inverse_window::<W>),
+            Some(drop_boxed::<W>),
+        )
+    };
+    unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+}
+
+/// Removes the function with the given name and amount of arguments
+pub(in crate::api) fn remove(sqlite: &Sqlite, name: &str, n_args: c_int) -> Result<(), Error> {
+    // Note: Registering a function without callbacks deletes it
+    unsafe { create_function(sqlite, name, n_args, 0, ptr::null_mut(), None, None, None, None) }
+}
+
+/// Calls a scalar function
+unsafe extern "C" fn call_scalar<F>(context: *mut ffi::sqlite3_context, argc: c_int, argv: *mut *mut ffi::sqlite3_value)
+where
+    F: Fn(&[ValueRef]) -> Result<SqliteType, Error>,
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a boxed `F` which lives until the destructor is called
+        let function = unsafe { &*(ffi::sqlite3_user_data(context) as *const F) };
+        let args = unsafe { ffiext::sqlite3_value_refs(argc, argv) }?;
+        function(&args)
+    }));
+
+    // Set the result
+    let result = result.unwrap_or_else(|_| Err(err!("Rust function panicked")));
+    unsafe { ffiext::sqlite3_result(context, result) };
+}
+
+/// Gets the aggregate state for the current group, and creates it if necessary
+///
+/// # Safety
+/// This function must only be called from within an aggregate callback of `A`.
+unsafe fn aggregate_state<'a, A>(context: *mut ffi::sqlite3_context, aggregate: &A) -> Result<&'a mut A::State, Error>
+where
+    A: Aggregate,
+{
+    // Get the aggregate context which holds a pointer to the boxed state
+    const SIZE: c_int = size_of::<*mut c_void>() as c_int;
+    let slot = unsafe { ffi::sqlite3_aggregate_context(context, SIZE) } as *mut *mut A::State;
+    let false = slot.is_null() else {
+        // SQLite only returns `NULL` if it is out of memory
+        return Err(err!("Failed to allocate aggregate context"));
+    };
+
+    // Create the state if necessary
+    // Note: The aggregate context is zeroed on allocation
+    if unsafe { *slot }.is_null() {
+        let state = Box::new(aggregate.init());
+        unsafe { *slot = Box::into_raw(state) };
+    }
+    Ok(unsafe { &mut **slot })
+}
+
+/// Takes the aggregate state for the current group out of the aggregate context if it exists
+///
+/// # Safety
+/// This function must only be called from within an aggregate callback of `A`.
+unsafe fn take_aggregate_state<A>(context: *mut ffi::sqlite3_context) -> Option<Box<A::State>>
+where
+    A: Aggregate,
+{
+    // Note: If the size is `0`, SQLite does not allocate the aggregate context but returns `NULL` if it does not exist
+    let slot = unsafe { ffi::sqlite3_aggregate_context(context, 0) } as *mut *mut A::State;
+    if slot.is_null() || unsafe { *slot }.is_null() {
+        return None;
+    }
+
+    // Take the state
+    let state = unsafe { Box::from_raw(*slot) };
+    unsafe { *slot = ptr::null_mut() };
+    Some(state)
+}
+
+/// Performs an aggregate step
+unsafe extern "C" fn step_aggregate<A>(
+    context: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) where
+    A: Aggregate,
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a boxed `A` which lives until the destructor is called
+        let aggregate = unsafe { &*(ffi::sqlite3_user_data(context) as *const A) };
+        let state = unsafe { aggregate_state(context, aggregate) }?;
+        let args = unsafe { ffiext::sqlite3_value_refs(argc, argv) }?;
+        aggregate.step(state, &args)
+    }));
+
+    // Set the error if any
+    // Note: The state is dropped by the final callback, which SQLite also calls if the query is aborted
+    let result = result.unwrap_or_else(|_| Err(err!("Rust aggregate function panicked")));
+    if let Err(e) = result {
+        unsafe { ffiext::sqlite3_result(context, Err(e)) };
+    }
+}
+
+/// Finalizes an aggregate
+unsafe extern "C" fn final_aggregate<A>(context: *mut ffi::sqlite3_context)
+where
+    A: Aggregate,
+{
+    // Take the state so that it is dropped in any case
+    let state = unsafe { take_aggregate_state::<A>(context) };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a boxed `A` which lives until the destructor is called
+        let aggregate = unsafe { &*(ffi::sqlite3_user_data(context) as *const A) };
+        let state = state.map(|state| *state).unwrap_or_else(|| aggregate.init());
+        aggregate.finalize(state)
+    }));
+
+    // Set the result
+    let result = result.unwrap_or_else(|_| Err(err!("Rust aggregate function panicked")));
+    unsafe { ffiext::sqlite3_result(context, result) };
+}
+
+/// Removes a row from a window
+unsafe extern "C" fn inverse_window<W>(
+    context: *mut ffi::sqlite3_context,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) where
+    W: WindowFunction,
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a boxed `W` which lives until the destructor is called
+        let window = unsafe { &*(ffi::sqlite3_user_data(context) as *const W) };
+        let state = unsafe { aggregate_state(context, window) }?;
+        let args = unsafe { ffiext::sqlite3_value_refs(argc, argv) }?;
+        window.inverse(state, &args)
+    }));
+
+    // Set the error if any
+    let result = result.unwrap_or_else(|_| Err(err!("Rust window function panicked")));
+    if let Err(e) = result {
+        unsafe { ffiext::sqlite3_result(context, Err(e)) };
+    }
+}
+
+/// Computes the current value of a window
+unsafe extern "C" fn value_window<W>(context: *mut ffi::sqlite3_context)
+where
+    W: WindowFunction,
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The application data is a boxed `W` which lives until the destructor is called
+        let window = unsafe { &*(ffi::sqlite3_user_data(context) as *const W) };
+        let state = unsafe { aggregate_state(context, window) }?;
+        window.value(state)
+    }));
+
+    // Set the result
+    let result = result.unwrap_or_else(|_| Err(err!("Rust window function panicked")));
+    unsafe { ffiext::sqlite3_result(context, result) };
+}
+
+/// Drops a boxed value that has been passed to SQLite as raw pointer
+pub(in crate::api) unsafe extern "C" fn drop_boxed<T>(data: *mut c_void) {
+    // Note: We must not unwind into SQLite, so we catch and discard any panic during drop
+    let data = unsafe { Box::from_raw(data as *mut T) };
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(data)));
+}
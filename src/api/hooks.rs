@@ -0,0 +1,397 @@
+//! Data-change, commit, rollback and pre-update hooks
+
+#[cfg(feature = "preupdate-hook")]
+use crate::api::ffiext;
+#[cfg(feature = "preupdate-hook")]
+use crate::api::types::SqliteType;
+#[cfg(feature = "preupdate-hook")]
+use crate::err;
+#[cfg(feature = "preupdate-hook")]
+use crate::error::Error;
+use crate::{ffi, Sqlite};
+use std::convert::Infallible;
+use std::ffi::{c_char, c_int, c_void, CStr};
+use std::fmt::{self, Debug, Formatter};
+use std::ops::Deref;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A type-erased, boxed callback
+pub(in crate::api) type BoxedHook = Box<dyn Send>;
+
+/// The kind of a data change reported by [`Sqlite::on_update`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UpdateOp {
+    /// A row has been inserted
+    Insert,
+    /// A row has been updated
+    Update,
+    /// A row has been deleted
+    Delete,
+}
+impl UpdateOp {
+    /// Maps a raw `SQLITE_INSERT`, `SQLITE_UPDATE` or `SQLITE_DELETE` operation
+    pub(in crate::api) const fn from_raw(op: c_int) -> Option<Self> {
+        match op {
+            ffi::SQLITE_INSERT => Some(Self::Insert),
+            ffi::SQLITE_UPDATE => Some(Self::Update),
+            ffi::SQLITE_DELETE => Some(Self::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// Boxed callbacks that are registered with SQLite but not owned by it
+///
+/// # Important
+/// SQLite does not free the application data of hooks, so we keep the boxed callbacks alive until they are replaced or
+/// the database is closed.
+#[derive(Default)]
+pub(in crate::api) struct Hooks {
+    /// The `sqlite3_collation_needed` callback
+    pub collation_needed: Option<BoxedHook>,
+    /// The `sqlite3_update_hook` callback
+    pub update: Option<BoxedHook>,
+    /// The `sqlite3_commit_hook` callback
+    pub commit: Option<BoxedHook>,
+    /// The `sqlite3_rollback_hook` callback
+    pub rollback: Option<BoxedHook>,
+    /// The `sqlite3_preupdate_hook` callback
+    #[cfg(feature = "preupdate-hook")]
+    pub preupdate: Option<BoxedHook>,
+    /// The `sqlite3_trace_v2` callback
+    pub trace: Option<BoxedHook>,
+}
+impl Hooks {
+    /// Locks the given hooks
+    pub fn lock(hooks: &Mutex<Self>) -> MutexGuard<'_, Self> {
+        // Note: The hooks are always in a consistent state, so we can safely ignore poisoning
+        hooks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers a callback with SQLite via `register` and stores it in the slot selected by `slot`
+    ///
+    /// # Note
+    /// The registration and the replacement are performed while holding both the database mutex and the hooks lock, so
+    /// that concurrent registrations cannot drop a callback that is still registered with SQLite. If the registration
+    /// fails, the slot is left unchanged and the new callback is dropped instead.
+    pub fn register<S, R, E>(sqlite: &Sqlite, slot: S, callback: Option<BoxedHook>, register: R) -> Result<(), E>
+    where
+        S: FnOnce(&mut Self) -> &mut Option<BoxedHook>,
+        R: FnOnce() -> Result<(), E>,
+    {
+        // Note: We always acquire the database mutex before the hooks lock, because SQLite holds the database mutex
+        //  while invoking callbacks, which may register hooks themselves
+        let mutex = unsafe { ffi::sqlite3_db_mutex(sqlite.raw.as_ptr()) };
+        unsafe { ffi::sqlite3_mutex_enter(mutex) };
+        let (result, unused) = {
+            let mut hooks = Self::lock(&sqlite.hooks);
+            match register() {
+                Ok(()) => (Ok(()), std::mem::replace(slot(&mut hooks), callback)),
+                Err(e) => (Err(e), callback),
+            }
+        };
+        unsafe { ffi::sqlite3_mutex_leave(mutex) };
+
+        // Note: We drop the unused callback after releasing the locks, in case its destructor accesses the database
+        drop(unused);
+        result
+    }
+}
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Hooks");
+        debug.field("collation_needed", &self.collation_needed.as_ref().map(|_| "<hook>"));
+        debug.field("update", &self.update.as_ref().map(|_| "<hook>"));
+        debug.field("commit", &self.commit.as_ref().map(|_| "<hook>"));
+        debug.field("rollback", &self.rollback.as_ref().map(|_| "<hook>"));
+        #[cfg(feature = "preupdate-hook")]
+        debug.field("preupdate", &self.preupdate.as_ref().map(|_| "<hook>"));
+        debug.field("trace", &self.trace.as_ref().map(|_| "<hook>"));
+        debug.finish()
+    }
+}
+
+/// The row change that is about to be performed, as reported by [`Sqlite::on_preupdate`]
+///
+/// # Note
+/// The accessor is only valid during the callback. For `WITHOUT ROWID` tables, the rowids are meaningless.
+#[cfg(feature = "preupdate-hook")]
+#[derive(Debug)]
+pub struct PreUpdate<'a> {
+    /// The database handle
+    raw: *mut ffi::sqlite3,
+    /// The operation
+    op: UpdateOp,
+    /// The schema name
+    database: &'a str,
+    /// The table name
+    table: &'a str,
+    /// The rowid of the row before the change
+    old_rowid: i64,
+    /// The rowid of the row after the change
+    new_rowid: i64,
+}
+#[cfg(feature = "preupdate-hook")]
+impl PreUpdate<'_> {
+    /// The operation that is about to be performed
+    pub fn op(&self) -> UpdateOp {
+        self.op
+    }
+    /// The schema name (e.g. `main`, `temp` or the name of an attached database)
+    pub fn database(&self) -> &str {
+        self.database
+    }
+    /// The table name
+    pub fn table(&self) -> &str {
+        self.table
+    }
+
+    /// The rowid of the row before the change, or `None` for inserts
+    pub fn old_rowid(&self) -> Option<i64> {
+        (self.op != UpdateOp::Insert).then_some(self.old_rowid)
+    }
+    /// The rowid of the row after the change, or `None` for deletes
+    pub fn new_rowid(&self) -> Option<i64> {
+        (self.op != UpdateOp::Delete).then_some(self.new_rowid)
+    }
+
+    /// The amount of columns of the affected row
+    pub fn count(&self) -> c_int {
+        unsafe { ffi::sqlite3_preupdate_count(self.raw) }
+    }
+    /// The trigger depth of the change, i.e. `0` for direct changes, `1` for changes by top-level triggers etc.
+    pub fn depth(&self) -> c_int {
+        unsafe { ffi::sqlite3_preupdate_depth(self.raw) }
+    }
+    /// The index of the column that is written via [`crate::api::blob::Blob`], or `None` if the change is not an
+    /// incremental BLOB write
+    ///
+    /// # Note
+    /// Incremental BLOB writes are reported as [`UpdateOp::Delete`], since the new values are not available yet.
+    pub fn blobwrite(&self) -> Option<c_int> {
+        let column = unsafe { ffi::sqlite3_preupdate_blobwrite(self.raw) };
+        (column >= 0).then_some(column)
+    }
+
+    /// The value of the given column before the change
+    ///
+    /// # Note
+    /// Column indices start with `0`. Old values are not available for inserts.
+    pub fn old(&self, column: c_int) -> Result<SqliteType, Error> {
+        // Validate the request
+        // Note: SQLite's behaviour is undefined for invalid requests
+        let true = self.op != UpdateOp::Insert else {
+            return Err(err!("Old values are not available for inserts"));
+        };
+        self.check_column(column)?;
+
+        // Get the value
+        let mut value = ptr::null_mut();
+        let retval = unsafe { ffi::sqlite3_preupdate_old(self.raw, column, &mut value) };
+        unsafe { ffiext::sqlite3_check_result(retval, self.raw) }?;
+        unsafe { ffiext::sqlite3_value_ref(value) }.map(SqliteType::from)
+    }
+    /// The value of the given column after the change
+    ///
+    /// # Note
+    /// Column indices start with `0`. New values are not available for deletes and incremental BLOB writes.
+    #[allow(clippy::new_ret_no_self, reason = "Mirrors `sqlite3_preupdate_new` and pairs with `old`")]
+    pub fn new(&self, column: c_int) -> Result<SqliteType, Error> {
+        // Validate the request
+        // Note: SQLite's behaviour is undefined for invalid requests
+        let true = self.op != UpdateOp::Delete else {
+            return Err(err!("New values are not available for deletes"));
+        };
+        self.check_column(column)?;
+
+        // Get the value
+        let mut value = ptr::null_mut();
+        let retval = unsafe { ffi::sqlite3_preupdate_new(self.raw, column, &mut value) };
+        unsafe { ffiext::sqlite3_check_result(retval, self.raw) }?;
+        unsafe { ffiext::sqlite3_value_ref(value) }.map(SqliteType::from)
+    }
+
+    /// Ensures that the given column index is within the affected row
+    fn check_column(&self, column: c_int) -> Result<(), Error> {
+        let true = (0..self.count()).contains(&column) else {
+            return Err(err!("Column index is out of bounds: {column}"));
+        };
+        Ok(())
+    }
+}
+
+/// A callback that is only invoked by SQLite
+///
+/// # Note
+/// All databases are opened with `SQLITE_OPEN_FULLMUTEX`, so SQLite holds the database mutex while invoking a callback
+/// and never invokes callbacks concurrently. Consequently, a shared callback only needs to be `Send`.
+#[repr(transparent)]
+pub(in crate::api) struct Serialized<F>(F);
+impl<F> Deref for Serialized<F> {
+    type Target = F;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+// Safety: The callback is only accessed by SQLite while holding the database mutex (see above)
+unsafe impl<F> Sync for Serialized<F> where F: Send {}
+
+/// Shares a callback so that it can be passed to SQLite as application data and stored in [`Hooks`]
+///
+/// # Note
+/// The application data is the raw pointer of an `Arc<Serialized<F>>`; the hook trampolines retain their own reference
+/// during a call, so that a callback stays alive even if it replaces itself.
+pub(in crate::api) fn share<F>(callback: F) -> (*mut c_void, BoxedHook)
+where
+    F: Send + 'static,
+{
+    let callback = Arc::new(Serialized(callback));
+    (Arc::as_ptr(&callback) as *mut c_void, Box::new(callback))
+}
+
+/// Retains a reference to a callback that has been shared via [`share`]
+///
+/// # Safety
+/// `data` must be the application data of a shared `F` which is still stored in [`Hooks`], and the caller must hold
+/// the database mutex (i.e. be called by SQLite).
+pub(in crate::api) unsafe fn retain<F>(data: *mut c_void) -> Arc<Serialized<F>> {
+    unsafe { Arc::increment_strong_count(data as *const Serialized<F>) };
+    unsafe { Arc::from_raw(data as *const Serialized<F>) }
+}
+
+/// Registers or removes the data-change callback
+pub(in crate::api) fn on_update<F>(sqlite: &Sqlite, callback: Option<F>)
+where
+    F: Fn(UpdateOp, &str, &str, i64) + Send + 'static,
+{
+    let (data, callback) = callback.map(share).unzip();
+    let trampoline = callback.as_ref().map(|_| call_update::<F> as _);
+    let register = || -> Result<(), Infallible> {
+        unsafe { ffi::sqlite3_update_hook(sqlite.raw.as_ptr(), trampoline, data.unwrap_or(ptr::null_mut())) };
+        Ok(())
+    };
+    let Ok(()) = Hooks::register(sqlite, |hooks| &mut hooks.update, callback, register);
+}
+
+/// Registers or removes the commit callback
+pub(in crate::api) fn on_commit<F>(sqlite: &Sqlite, callback: Option<F>)
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    let (data, callback) = callback.map(share).unzip();
+    let trampoline = callback.as_ref().map(|_| call_commit::<F> as _);
+    let register = || -> Result<(), Infallible> {
+        unsafe { ffi::sqlite3_commit_hook(sqlite.raw.as_ptr(), trampoline, data.unwrap_or(ptr::null_mut())) };
+        Ok(())
+    };
+    let Ok(()) = Hooks::register(sqlite, |hooks| &mut hooks.commit, callback, register);
+}
+
+/// Registers or removes the rollback callback
+pub(in crate::api) fn on_rollback<F>(sqlite: &Sqlite, callback: Option<F>)
+where
+    F: Fn() + Send + 'static,
+{
+    let (data, callback) = callback.map(share).unzip();
+    let trampoline = callback.as_ref().map(|_| call_rollback::<F> as _);
+    let register = || -> Result<(), Infallible> {
+        unsafe { ffi::sqlite3_rollback_hook(sqlite.raw.as_ptr(), trampoline, data.unwrap_or(ptr::null_mut())) };
+        Ok(())
+    };
+    let Ok(()) = Hooks::register(sqlite, |hooks| &mut hooks.rollback, callback, register);
+}
+
+/// Registers or removes the pre-update callback
+#[cfg(feature = "preupdate-hook")]
+pub(in crate::api) fn on_preupdate<F>(sqlite: &Sqlite, callback: Option<F>)
+where
+    F: Fn(&PreUpdate) + Send + 'static,
+{
+    let (data, callback) = callback.map(share).unzip();
+    let trampoline = callback.as_ref().map(|_| call_preupdate::<F> as _);
+    let register = || -> Result<(), Infallible> {
+        unsafe { ffi::sqlite3_preupdate_hook(sqlite.raw.as_ptr(), trampoline, data.unwrap_or(ptr::null_mut())) };
+        Ok(())
+    };
+    let Ok(()) = Hooks::register(sqlite, |hooks| &mut hooks.preupdate, callback, register);
+}
+
+/// Calls the data-change callback
+unsafe extern "C" fn call_update<F>(
+    data: *mut c_void,
+    op: c_int,
+    database: *const c_char,
+    table: *const c_char,
+    rowid: ffi::sqlite3_int64,
+) where
+    F: Fn(UpdateOp, &str, &str, i64),
+{
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: SQLite only reports inserts, updates and deletes
+        let Some(op) = UpdateOp::from_raw(op) else {
+            return;
+        };
+
+        // Call the callback
+        let callback = unsafe { retain::<F>(data) };
+        let database = unsafe { CStr::from_ptr(database) }.to_string_lossy();
+        let table = unsafe { CStr::from_ptr(table) }.to_string_lossy();
+        callback(op, &database, &table, rowid);
+    }));
+}
+
+/// Calls the commit callback
+unsafe extern "C" fn call_commit<F>(data: *mut c_void) -> c_int
+where
+    F: Fn() -> bool,
+{
+    let veto = panic::catch_unwind(AssertUnwindSafe(|| {
+        let callback = unsafe { retain::<F>(data) };
+        callback()
+    }));
+
+    // Note: If the callback panics, we veto the commit to be on the safe side
+    c_int::from(veto.unwrap_or(true))
+}
+
+/// Calls the rollback callback
+unsafe extern "C" fn call_rollback<F>(data: *mut c_void)
+where
+    F: Fn(),
+{
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let callback = unsafe { retain::<F>(data) };
+        callback()
+    }));
+}
+
+/// Calls the pre-update callback
+#[cfg(feature = "preupdate-hook")]
+unsafe extern "C" fn call_preupdate<F>(
+    data: *mut c_void,
+    raw: *mut ffi::sqlite3,
+    op: c_int,
+    database: *const c_char,
+    table: *const c_char,
+    old_rowid: ffi::sqlite3_int64,
+    new_rowid: ffi::sqlite3_int64,
+) where
+    F: Fn(&PreUpdate),
+{
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: SQLite only reports inserts, updates and deletes
+        let Some(op) = UpdateOp::from_raw(op) else {
+            return;
+        };
+
+        // Call the callback
+        let callback = unsafe { retain::<F>(data) };
+        let database = unsafe { CStr::from_ptr(database) }.to_string_lossy();
+        let table = unsafe { CStr::from_ptr(table) }.to_string_lossy();
+        let preupdate = PreUpdate { raw, op, database: &database, table: &table, old_rowid, new_rowid };
+        callback(&preupdate);
+    }));
+}
@@ -2,8 +2,22 @@
 #![cfg(feature = "api")]
 
 pub mod answer;
+pub mod backup;
+pub mod blob;
+mod cache;
+pub mod collation;
 pub mod ffiext;
+pub mod function;
+pub mod hooks;
 pub mod query;
 pub mod row;
+pub mod savepoint;
+mod serialize;
+#[cfg(feature = "session")]
+pub mod session;
 pub mod sqlite;
+pub mod trace;
+pub mod transaction;
+mod tuples;
 pub mod types;
+pub mod vtab;
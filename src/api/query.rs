@@ -2,9 +2,22 @@
 
 use crate::api::answer::Answer;
 use crate::api::ffiext::{self, PointerMut};
-use crate::api::types::SqliteType;
+use crate::api::types::{ArrayRef, SqliteType, ValueRef};
 use crate::error::Error;
 use crate::{err, ffi, Sqlite};
+use std::ffi::{c_void, CStr, CString};
+
+/// A type that can be bound to the parameters of an SQLite query
+pub trait ToParams {
+    /// Binds `self` to the parameters of the given query
+    fn to_params<'db>(&self, query: Query<'db>) -> Result<Query<'db>, Error>;
+}
+
+/// A tuple of values that can be bound positionally to the parameters of an SQLite query
+pub trait BindAll {
+    /// Binds the values to the parameters `1..=N` of the given query
+    fn bind_all<'db>(self, query: Query<'db>) -> Result<Query<'db>, Error>;
+}
 
 /// An SQLite query
 #[derive(Debug)]
@@ -31,11 +44,69 @@ impl<'db> Query<'db> {
             SqliteType::Null => self.bind_null(column)?,
             SqliteType::Integer(value) => self.bind_integer(column, value)?,
             SqliteType::Real(value) => self.bind_real(column, value)?,
-            SqliteType::Text(value) => self.bind_text(column, value)?,
-            SqliteType::Blob(value) => self.bind_blob(column, value)?,
+            // Note: SQLite copies the value immediately
+            SqliteType::Text(value) => unsafe { self.bind_text(column, value.as_bytes(), ffi::sqlite3_transient()) }?,
+            SqliteType::Blob(value) => unsafe { self.bind_blob(column, &value, ffi::sqlite3_transient()) }?,
         }
         Ok(self)
     }
+    /// Binds a value to a named parameter
+    ///
+    /// # Note
+    /// The name must include the prefix character, i.e. `:name`, `@name` or `$name`
+    pub fn bind_named<T>(self, name: &str, value: T) -> Result<Self, Error>
+    where
+        SqliteType: TryFrom<T>,
+        <SqliteType as TryFrom<T>>::Error: std::error::Error + Send + 'static,
+    {
+        // Resolve the parameter index and bind the value
+        let column = self.parameter_index(name).ok_or_else(|| err!("Unknown parameter name: {name}"))?;
+        self.bind(column, value)
+    }
+    /// Binds a value to all named parameters with the given name, regardless of their prefix (i.e. `:name`, `@name`
+    /// and `$name`)
+    ///
+    /// # Note
+    /// Unlike [`Query::bind_named`], the name must not include the prefix character. It is an error if the statement
+    /// does not contain any such parameter.
+    pub fn bind_field<T>(mut self, name: &str, value: T) -> Result<Self, Error>
+    where
+        SqliteType: TryFrom<T>,
+        <SqliteType as TryFrom<T>>::Error: std::error::Error + Send + 'static,
+    {
+        // Create intermediate value
+        let value =
+            SqliteType::try_from(value).map_err(|e| err!(with: e, "Failed to convert value into SQLite type"))?;
+
+        // Bind the value to all matching parameters
+        let mut found = false;
+        for prefix in [':', '@', '$'] {
+            if let Some(column) = self.parameter_index(&format!("{prefix}{name}")) {
+                self = self.bind::<SqliteType>(column, value.clone())?;
+                found = true;
+            }
+        }
+
+        // Ensure that the value has been bound at least once
+        match found {
+            true => Ok(self),
+            false => Err(err!("Unknown parameter name: {name}")),
+        }
+    }
+    /// Binds the given parameters
+    pub fn bind_params<T>(self, params: &T) -> Result<Self, Error>
+    where
+        T: ToParams + ?Sized,
+    {
+        params.to_params(self)
+    }
+    /// Binds all values of the given tuple positionally, starting with index `1`
+    pub fn bind_all<T>(self, values: T) -> Result<Self, Error>
+    where
+        T: BindAll,
+    {
+        values.bind_all(self)
+    }
     /// Binds a NULL value
     fn bind_null(&self, column: std::ffi::c_int) -> Result<(), Error> {
         let retval = unsafe { ffi::sqlite3_bind_null(self.raw.as_ptr(), column) };
@@ -52,35 +123,210 @@ impl<'db> Query<'db> {
         unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }
     }
     /// Binds a TEXT value
-    fn bind_text(&self, column: std::ffi::c_int, value: String) -> Result<(), Error> {
+    ///
+    /// # Safety
+    /// Unless `destructor` is `SQLITE_TRANSIENT`, the value must remain valid until SQLite calls the destructor, or - if
+    /// the destructor is `SQLITE_STATIC` - until the binding is replaced or cleared or the statement is finalized.
+    unsafe fn bind_text(
+        &self,
+        column: std::ffi::c_int,
+        value: &[u8],
+        destructor: ffi::sqlite3_destructor_type,
+    ) -> Result<(), Error> {
         let retval = unsafe {
-            // Bind the text value and instruct SQLite to immediately copy the value
+            // Bind the text value with the given destructor
             ffi::sqlite3_bind_text64(
                 self.raw.as_ptr(),
                 column,
                 value.as_ptr() as _,
                 value.len() as _,
-                ffi::sqlite3_transient(),
+                destructor,
                 ffi::SQLITE_UTF8 as _,
             )
         };
         unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }
     }
     /// Binds a BLOB value
-    fn bind_blob(&self, column: std::ffi::c_int, value: Vec<u8>) -> Result<(), Error> {
+    ///
+    /// # Safety
+    /// Unless `destructor` is `SQLITE_TRANSIENT`, the value must remain valid until SQLite calls the destructor, or - if
+    /// the destructor is `SQLITE_STATIC` - until the binding is replaced or cleared or the statement is finalized.
+    unsafe fn bind_blob(
+        &self,
+        column: std::ffi::c_int,
+        value: &[u8],
+        destructor: ffi::sqlite3_destructor_type,
+    ) -> Result<(), Error> {
         let retval = unsafe {
-            // Bind the blob value and instruct SQLite to immediately copy the value
-            ffi::sqlite3_bind_blob64(
-                self.raw.as_ptr(),
-                column,
-                value.as_ptr() as _,
-                value.len() as _,
-                ffi::sqlite3_transient(),
-            )
+            // Bind the blob value with the given destructor
+            ffi::sqlite3_bind_blob64(self.raw.as_ptr(), column, value.as_ptr() as _, value.len() as _, destructor)
         };
         unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }
     }
 
+    /// Binds a borrowed value without copying it
+    ///
+    /// # Note
+    /// The value must outlive the query and the resulting answer, which is enforced by shortening the lifetime of the
+    /// returned query accordingly.
+    ///
+    /// # Important
+    /// Sadly, unless manually specified with `?NNN`, default column indices for binding start with `1` 😭
+    pub fn bind_ref<'p, T>(self, column: std::ffi::c_int, value: T) -> Result<Query<'p>, Error>
+    where
+        'db: 'p,
+        T: Into<ValueRef<'p>>,
+    {
+        match value.into() {
+            ValueRef::Null => self.bind_null(column)?,
+            ValueRef::Integer(value) => self.bind_integer(column, value)?,
+            ValueRef::Real(value) => self.bind_real(column, value)?,
+            // Note: The value lives at least as long as the returned query or any answer or row derived from it, so
+            //  SQLite can safely reference it until the statement is finalized
+            ValueRef::Text(value) => unsafe { self.bind_text(column, value.as_bytes(), ffi::SQLITE_STATIC) }?,
+            ValueRef::Blob(value) => unsafe { self.bind_blob(column, value, ffi::SQLITE_STATIC) }?,
+        }
+        Ok(self)
+    }
+    /// Binds a value and hands the ownership of TEXT and BLOB values over to SQLite instead of copying them
+    ///
+    /// # Important
+    /// Sadly, unless manually specified with `?NNN`, default column indices for binding start with `1` 😭
+    pub fn bind_owned<T>(self, column: std::ffi::c_int, value: T) -> Result<Self, Error>
+    where
+        SqliteType: TryFrom<T>,
+        <SqliteType as TryFrom<T>>::Error: std::error::Error + Send + 'static,
+    {
+        // Create intermediate value and bind it
+        let value =
+            SqliteType::try_from(value).map_err(|e| err!(with: e, "Failed to convert value into SQLite type"))?;
+        match value {
+            SqliteType::Null => self.bind_null(column)?,
+            SqliteType::Integer(value) => self.bind_integer(column, value)?,
+            SqliteType::Real(value) => self.bind_real(column, value)?,
+            SqliteType::Text(value) => {
+                // Note: The buffer remains valid until SQLite calls the destructor
+                let (value, destructor) = ffiext::sqlite3_handover(value.into_bytes());
+                unsafe { self.bind_text(column, &*value, destructor) }?
+            }
+            SqliteType::Blob(value) => {
+                // Note: The buffer remains valid until SQLite calls the destructor
+                let (value, destructor) = ffiext::sqlite3_handover(value);
+                unsafe { self.bind_blob(column, &*value, destructor) }?
+            }
+        }
+        Ok(self)
+    }
+
+    /// Binds an array to the first parameter of a `carray()` table-valued function, e.g. `WHERE id IN carray(?1)`
+    ///
+    /// # Note
+    /// SQLite copies the array immediately. Strings must not contain `NUL`-bytes.
+    ///
+    /// # Important
+    /// Sadly, unless manually specified with `?NNN`, default column indices for binding start with `1` 😭
+    pub fn bind_array<'a, T>(self, column: std::ffi::c_int, values: T) -> Result<Self, Error>
+    where
+        T: Into<ArrayRef<'a>>,
+    {
+        /// A BLOB element of a `carray()` array which has the same layout as `struct iovec`
+        #[repr(C)]
+        struct IoVec {
+            /// The BLOB data
+            base: *const u8,
+            /// The BLOB length
+            len: usize,
+        }
+
+        // Convert the array into the C representation
+        // Note: The intermediate buffers must live until SQLite has copied the array
+        let (mut strings, mut texts, mut blobs) = (Vec::new(), Vec::new(), Vec::new());
+        let (data, len, flags) = match values.into() {
+            ArrayRef::Integer(values) => (values.as_ptr() as *const c_void, values.len(), ffi::SQLITE_CARRAY_INT64),
+            ArrayRef::Real(values) => (values.as_ptr() as *const c_void, values.len(), ffi::SQLITE_CARRAY_DOUBLE),
+            ArrayRef::Text(values) => {
+                // Create the C strings and collect the pointers
+                for value in values {
+                    let value = CString::new(value.as_str()).map_err(|e| err!(with: e, "Invalid array string"))?;
+                    strings.push(value);
+                }
+                texts.extend(strings.iter().map(|string| string.as_ptr()));
+                (texts.as_ptr() as *const c_void, texts.len(), ffi::SQLITE_CARRAY_TEXT)
+            }
+            ArrayRef::Blob(values) => {
+                blobs.extend(values.iter().map(|value| IoVec { base: value.as_ptr(), len: value.len() }));
+                (blobs.as_ptr() as *const c_void, blobs.len(), ffi::SQLITE_CARRAY_BLOB)
+            }
+        };
+
+        // Bind the array
+        let len = std::ffi::c_int::try_from(len).map_err(|e| err!(with: e, "Array is too large"))?;
+        let destructor = match len {
+            // Note: SQLite fails to copy empty arrays, but it also never reads from them
+            0 => ffi::SQLITE_STATIC,
+            _ => unsafe { ffi::sqlite3_transient() },
+        };
+        let retval = unsafe {
+            // Note: SQLite does not modify the array, even though the pointer is mutable
+            ffi::sqlite3_carray_bind(self.raw.as_ptr(), column, data as *mut c_void, len, flags, destructor)
+        };
+        unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }?;
+
+        // Note: SQLite has copied the array, so the intermediate buffers can be dropped now
+        drop((texts, strings, blobs));
+        Ok(self)
+    }
+
+    /// Binds a BLOB of `len` zero-bytes without allocating it, e.g. to preallocate space for incremental BLOB I/O
+    ///
+    /// # Important
+    /// Sadly, unless manually specified with `?NNN`, default column indices for binding start with `1` 😭
+    pub fn bind_zeroblob(self, column: std::ffi::c_int, len: u64) -> Result<Self, Error> {
+        let retval = unsafe { ffi::sqlite3_bind_zeroblob64(self.raw.as_ptr(), column, len) };
+        unsafe { ffiext::sqlite3_check_result(retval, self.sqlite.raw.as_ptr()) }?;
+        Ok(self)
+    }
+
+    /// Resets all bound values to NULL
+    pub fn clear_bindings(self) -> Self {
+        // Note: `sqlite3_clear_bindings` always succeeds
+        unsafe { ffi::sqlite3_clear_bindings(self.raw.as_ptr()) };
+        self
+    }
+
+    /// The amount of parameters in the statement
+    ///
+    /// # Note
+    /// This is the largest parameter index, which may differ from the amount of distinct parameters if `?NNN`
+    /// parameters are used
+    pub fn parameter_count(&self) -> std::ffi::c_int {
+        unsafe { ffi::sqlite3_bind_parameter_count(self.raw.as_ptr()) }
+    }
+    /// The index of the parameter with the given name including its prefix character, or `None` if the statement does
+    /// not contain such a parameter
+    pub fn parameter_index(&self, name: &str) -> Option<std::ffi::c_int> {
+        let name = CString::new(name).ok()?;
+        let column = unsafe { ffi::sqlite3_bind_parameter_index(self.raw.as_ptr(), name.as_ptr()) };
+        (column > 0).then_some(column)
+    }
+    /// The name of the parameter at the given index including its prefix character, or `None` if the parameter is
+    /// nameless (i.e. `?`) or the index is out of range
+    ///
+    /// # Important
+    /// Sadly, parameter indices start with `1` 😭
+    pub fn parameter_name(&self, column: std::ffi::c_int) -> Option<&str> {
+        // Get the parameter name
+        let name = unsafe { ffi::sqlite3_bind_parameter_name(self.raw.as_ptr(), column) };
+        let false = name.is_null() else {
+            // Parameter is nameless or does not exist
+            return None;
+        };
+
+        // Note: The name is a substring of the UTF-8 SQL text, which lives as long as the statement
+        let name = unsafe { CStr::from_ptr(name) };
+        name.to_str().ok()
+    }
+
     /// Executes the query and gets the next result row if any
     pub fn execute(self) -> Result<Answer<'db>, Error> {
         // Create the result object and do a step to make sure the query is actually executed
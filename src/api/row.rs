@@ -1,11 +1,17 @@
 //! An SQLite query result row
 
 use crate::api::ffiext::PointerMutFlex;
-use crate::api::types::SqliteType;
+use crate::api::types::{SqliteType, ValueRef};
 use crate::error::Error;
 use crate::{err, ffi};
 use std::ffi::CStr;
 
+/// A type that can be constructed from an SQLite result row
+pub trait FromRow: Sized {
+    /// Constructs `Self` from the given row
+    fn from_row(row: &Row) -> Result<Self, Error>;
+}
+
 /// An SQLite result row
 #[derive(Debug)]
 pub struct Row<'stmt> {
@@ -37,52 +43,127 @@ impl Row<'_> {
         SqliteType: TryInto<T>,
         <SqliteType as TryInto<T>>::Error: std::error::Error + Send + 'static,
     {
+        // Copy the value and convert it into the requested type
+        let value = SqliteType::from(self.read_ref(column)?);
+        value.try_into().map_err(|e| err!(with: e, "Failed to load from SQLite type"))
+    }
+    /// Reads a borrowed reference to the value for the requested column from the current row without copying it
+    ///
+    /// # Note
+    /// Column indices for reading start with `0`. The returned reference is only valid as long as the current row, so
+    /// it cannot be held across a step to the next row:
+    /// ```compile_fail
+    /// # use sqlite_tiny::Sqlite;
+    /// # fn main() -> Result<(), sqlite_tiny::error::Error> {
+    /// let database = Sqlite::uri("file:test.db?mode=memory")?;
+    /// let mut answer = database.query("SELECT 'a' UNION ALL SELECT 'b'")?.execute()?;
+    /// let first = answer.next_row()?.expect("missing row");
+    /// let value = first.read_ref(0)?;
+    /// let _second = answer.next_row()?;
+    /// println!("{value:?}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn read_ref(&self, column: std::ffi::c_int) -> Result<ValueRef<'_>, Error> {
         // Get the type and read the value as said type
         let type_ = unsafe { ffi::sqlite3_column_type(self.raw.as_ptr(), column) };
-        let value = match type_ {
-            ffi::SQLITE_NULL => SqliteType::Null,
-            ffi::SQLITE_INTEGER => self.read_integer(column)?,
-            ffi::SQLITE_FLOAT => self.read_real(column)?,
-            ffi::SQLITE_TEXT => self.read_text(column)?,
-            ffi::SQLITE_BLOB => self.read_blob(column)?,
-            _ => return Err(err!("Unknown SQLite column type: {type_}")),
-        };
+        match type_ {
+            ffi::SQLITE_NULL => Ok(ValueRef::Null),
+            ffi::SQLITE_INTEGER => Ok(self.read_integer(column)),
+            ffi::SQLITE_FLOAT => Ok(self.read_real(column)),
+            ffi::SQLITE_TEXT => self.read_text(column),
+            ffi::SQLITE_BLOB => Ok(self.read_blob(column)),
+            _ => Err(err!("Unknown SQLite column type: {type_}")),
+        }
+    }
+    /// Reads the value for the column with the given name from the current row
+    ///
+    /// # Note
+    /// Like in SQL, column names are matched case-insensitively; if multiple columns share the same name, the first one
+    /// is used
+    pub fn read_by_name<T>(&self, name: &str) -> Result<T, Error>
+    where
+        SqliteType: TryInto<T>,
+        <SqliteType as TryInto<T>>::Error: std::error::Error + Send + 'static,
+    {
+        let column = self.column_index(name).ok_or_else(|| err!("Unknown column name: {name}"))?;
+        self.read(column)
+    }
+    /// Gets the index of the column with the given name, or `None` if there is no such column
+    ///
+    /// # Note
+    /// Like in SQL, column names are matched case-insensitively; if multiple columns share the same name, the first one
+    /// is used
+    pub fn column_index(&self, name: &str) -> Option<std::ffi::c_int> {
+        let columns = unsafe { ffi::sqlite3_column_count(self.raw.as_ptr()) };
+        (0..columns).find(|&column| {
+            // Get the column name
+            let name_ = unsafe { ffi::sqlite3_column_name(self.raw.as_ptr(), column) };
+            let false = name_.is_null() else {
+                // The column name is not available
+                return false;
+            };
 
-        // Convert value into requested type
-        value.try_into().map_err(|e| err!(with: e, "Failed to load from SQLite type"))
+            // Compare the column name
+            let name_ = unsafe { CStr::from_ptr(name_) };
+            name_.to_bytes().eq_ignore_ascii_case(name.as_bytes())
+        })
+    }
+
+    /// Reads the columns `0..N` of the current row into a tuple
+    ///
+    /// # Note
+    /// The amount of columns in the row must match the tuple arity.
+    pub fn read_tuple<T>(&self) -> Result<T, Error>
+    where
+        T: FromRow,
+    {
+        T::from_row(self)
     }
+
     /// Reads an INTEGER value from the given column
-    fn read_integer(&self, column: std::ffi::c_int) -> Result<SqliteType, Error> {
+    fn read_integer(&self, column: std::ffi::c_int) -> ValueRef<'_> {
         let value = unsafe { ffi::sqlite3_column_int64(self.raw.as_ptr(), column) };
-        Ok(SqliteType::Integer(value))
+        ValueRef::Integer(value)
     }
     /// Reads a REAL value from the given column
-    fn read_real(&self, column: std::ffi::c_int) -> Result<SqliteType, Error> {
+    fn read_real(&self, column: std::ffi::c_int) -> ValueRef<'_> {
         let value = unsafe { ffi::sqlite3_column_double(self.raw.as_ptr(), column) };
-        Ok(SqliteType::Real(value))
+        ValueRef::Real(value)
     }
     /// Reads a TEXT value from the given column
-    fn read_text(&self, column: std::ffi::c_int) -> Result<SqliteType, Error> {
+    fn read_text(&self, column: std::ffi::c_int) -> Result<ValueRef<'_>, Error> {
         // Get text value
         let chars = unsafe { ffi::sqlite3_column_text(self.raw.as_ptr(), column) };
-        let text = unsafe { CStr::from_ptr(chars as _) };
+        let false = chars.is_null() else {
+            // SQLite only returns `NULL` for a TEXT value if it is out of memory
+            return Err(err!("Failed to read SQLite string"));
+        };
+
+        // Get text length and borrow bytes
+        // Note: The bytes are valid until the statement is stepped, reset or finalized, which requires a mutable borrow
+        //  of the row or its answer
+        let len = unsafe { ffi::sqlite3_column_bytes(self.raw.as_ptr(), column) };
+        let bytes = unsafe { std::slice::from_raw_parts(chars, len as usize) };
 
         // Get rust string
-        let text = text.to_str().map_err(|e| err!(with: e, "SQLite string is not valid UTF-8"))?;
-        Ok(SqliteType::Text(text.to_string()))
+        let text = std::str::from_utf8(bytes).map_err(|e| err!(with: e, "SQLite string is not valid UTF-8"))?;
+        Ok(ValueRef::Text(text))
     }
     /// Reads a BLOB value from the given column
-    fn read_blob(&self, column: std::ffi::c_int) -> Result<SqliteType, Error> {
+    fn read_blob(&self, column: std::ffi::c_int) -> ValueRef<'_> {
         // Get blob value
         let data = unsafe { ffi::sqlite3_column_blob(self.raw.as_ptr(), column) };
         let false = data.is_null() else {
             // SQLite has a "special" way of handling empty blobs
-            return Ok(SqliteType::Blob(Vec::new()));
+            return ValueRef::Blob(&[]);
         };
 
-        // Get blob length and copy bytes
+        // Get blob length and borrow bytes
+        // Note: The bytes are valid until the statement is stepped, reset or finalized, which requires a mutable borrow
+        //  of the row or its answer
         let len = unsafe { ffi::sqlite3_column_bytes(self.raw.as_ptr(), column) };
         let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, len as usize) };
-        Ok(SqliteType::Blob(bytes.to_vec()))
+        ValueRef::Blob(bytes)
     }
 }
@@ -0,0 +1,77 @@
+//! An SQLite savepoint
+
+use crate::api::query::Query;
+use crate::error::Error;
+use crate::Sqlite;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A process-wide counter to generate unique savepoint names
+static SAVEPOINT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// An SQLite savepoint which is rolled back to and released on drop unless it has been released
+///
+/// # Note
+/// Savepoints can be nested to any depth. While a nested savepoint is alive, the outer savepoint or transaction is
+/// mutably borrowed and cannot be used.
+#[derive(Debug)]
+pub struct Savepoint<'db> {
+    /// The database
+    sqlite: &'db Sqlite,
+    /// The unique savepoint name
+    name: String,
+    /// Whether the savepoint has already been released or rolled back
+    finished: bool,
+}
+impl<'db> Savepoint<'db> {
+    /// Creates a new savepoint with a generated unique name
+    pub(in crate::api) fn begin(sqlite: &'db Sqlite) -> Result<Self, Error> {
+        // Generate a unique name and create the savepoint
+        let counter = SAVEPOINT_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let name = format!("sqlite_tiny_savepoint_{counter}");
+        sqlite.execute(&format!("SAVEPOINT {name}"))?;
+        Ok(Self { sqlite, name, finished: false })
+    }
+
+    /// The generated name of the savepoint
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Creates a new nested savepoint
+    pub fn savepoint(&mut self) -> Result<Savepoint<'_>, Error> {
+        Savepoint::begin(self.sqlite)
+    }
+
+    /// Creates a new query from a **single** SQL statement within the savepoint
+    pub fn query<'a>(&'a self, query: &str) -> Result<Query<'a>, Error> {
+        self.sqlite.query(query)
+    }
+
+    /// Executes one or more SQL queries within the savepoint
+    pub fn execute(&self, query: &str) -> Result<(), Error> {
+        self.sqlite.execute(query)
+    }
+
+    /// Releases the savepoint, i.e. merges its changes into the enclosing savepoint or transaction
+    pub fn release(mut self) -> Result<(), Error> {
+        // Note: If the release fails, the savepoint is still rolled back on drop
+        self.sqlite.execute(&format!("RELEASE {}", self.name))?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Rolls back all changes since the savepoint was created and releases it
+    pub fn rollback(mut self) -> Result<(), Error> {
+        self.finished = true;
+        self.sqlite.execute(&format!("ROLLBACK TO {name}; RELEASE {name}", name = self.name))
+    }
+}
+impl Drop for Savepoint<'_> {
+    fn drop(&mut self) {
+        // Roll back to and release the savepoint if it has not been finished yet
+        // Note: The savepoint might have been discarded by SQLite already (e.g. by a `ROLLBACK`), so we check first
+        if !self.finished && self.sqlite.in_transaction() {
+            let _ = self.sqlite.execute(&format!("ROLLBACK TO {name}; RELEASE {name}", name = self.name));
+        }
+    }
+}
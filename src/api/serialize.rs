@@ -0,0 +1,52 @@
+//! Serialization of databases into and from bytes
+
+use crate::api::ffiext;
+use crate::error::Error;
+use crate::{err, ffi, Sqlite};
+use std::ffi::{c_void, CString};
+
+/// Serializes the given schema into a byte vector
+pub(in crate::api) fn serialize(sqlite: &Sqlite, schema: &str) -> Result<Vec<u8>, Error> {
+    let schema = CString::new(schema).map_err(|e| err!(with: e, "Invalid schema name"))?;
+
+    // Serialize the database
+    let mut len = 0;
+    let data = unsafe { ffi::sqlite3_serialize(sqlite.raw.as_ptr(), schema.as_ptr(), &mut len, 0) };
+    let false = data.is_null() else {
+        // SQLite returns `NULL` if the schema does not exist or if it is out of memory
+        return Err(err!("Failed to serialize database"));
+    };
+
+    // Copy the data into Rust memory
+    // Note: The data is allocated by SQLite and must be freed with `sqlite3_free`
+    let bytes = match usize::try_from(len) {
+        Ok(len) => Ok(unsafe { std::slice::from_raw_parts(data, len) }.to_vec()),
+        Err(e) => Err(err!(with: e, "Invalid serialized database size")),
+    };
+    unsafe { ffi::sqlite3_free(data as *mut c_void) };
+    bytes
+}
+
+/// Replaces the given schema with the given serialized database
+pub(in crate::api) fn deserialize(sqlite: &Sqlite, schema: &str, bytes: &[u8], read_only: bool) -> Result<(), Error> {
+    let schema = CString::new(schema).map_err(|e| err!(with: e, "Invalid schema name"))?;
+    let len = i64::try_from(bytes.len()).map_err(|e| err!(with: e, "Serialized database is too large"))?;
+
+    // Copy the bytes into memory that is owned by SQLite
+    // Note: We always allocate at least one byte, because SQLite returns `NULL` for zero-sized allocations
+    let data = unsafe { ffi::sqlite3_malloc64(bytes.len().max(1) as u64) } as *mut u8;
+    let false = data.is_null() else {
+        // SQLite is out of memory
+        return Err(err!("Failed to allocate serialized database"));
+    };
+    unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), data, bytes.len()) };
+
+    // Hand the buffer over to SQLite, which may only grow it if it is writable
+    // Note: With `SQLITE_DESERIALIZE_FREEONCLOSE`, SQLite frees the buffer even if the call fails
+    let flags = match read_only {
+        true => ffi::SQLITE_DESERIALIZE_FREEONCLOSE | ffi::SQLITE_DESERIALIZE_READONLY,
+        false => ffi::SQLITE_DESERIALIZE_FREEONCLOSE | ffi::SQLITE_DESERIALIZE_RESIZEABLE,
+    };
+    let retval = unsafe { ffi::sqlite3_deserialize(sqlite.raw.as_ptr(), schema.as_ptr(), data, len, len, flags as _) };
+    unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+}
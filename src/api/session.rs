@@ -0,0 +1,423 @@
+//! Changesets and patchsets via the session extension
+
+use crate::api::ffiext::{self, PointerMut};
+use crate::api::hooks::UpdateOp;
+use crate::api::types::SqliteType;
+use crate::error::Error;
+use crate::{err, ffi, Sqlite};
+use std::ffi::{c_int, c_void, CString};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// A function to read the value of a column from a changeset iterator
+type ValueGetter = unsafe extern "C" fn(*mut ffi::sqlite3_changeset_iter, c_int, *mut *mut ffi::sqlite3_value) -> c_int;
+
+/// A session that records the changes to the attached tables of a database
+///
+/// # Note
+/// Only changes to tables with a declared `PRIMARY KEY` are recorded. Changes are recorded per row, so the changeset
+/// contains the net effect of all changes since the session has been created.
+#[derive(Debug)]
+pub struct Session<'db> {
+    /// The database
+    #[allow(dead_code, reason = "The database must outlive the session")]
+    sqlite: &'db Sqlite,
+    /// The session handle
+    raw: *mut ffi::sqlite3_session,
+}
+impl<'db> Session<'db> {
+    /// Creates a new session for the given schema (e.g. `main`)
+    pub(in crate::api) fn new(sqlite: &'db Sqlite, schema: &str) -> Result<Self, Error> {
+        let schema = CString::new(schema).map_err(|e| err!(with: e, "Invalid schema name"))?;
+
+        // Create the session
+        let mut raw = ptr::null_mut();
+        let retval = unsafe { ffi::sqlite3session_create(sqlite.raw.as_ptr(), schema.as_ptr(), &mut raw) };
+        unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
+        Ok(Self { sqlite, raw })
+    }
+
+    /// Attaches the given table, so that its changes are recorded
+    pub fn attach(&mut self, table: &str) -> Result<(), Error> {
+        let table = CString::new(table).map_err(|e| err!(with: e, "Invalid table name"))?;
+        let retval = unsafe { ffi::sqlite3session_attach(self.raw, table.as_ptr()) };
+        unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }
+    }
+    /// Attaches all tables, including tables that are created later
+    pub fn attach_all(&mut self) -> Result<(), Error> {
+        let retval = unsafe { ffi::sqlite3session_attach(self.raw, ptr::null()) };
+        unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }
+    }
+
+    /// Enables or disables the recording of changes (enabled by default)
+    pub fn set_enabled(&mut self, enabled: bool) {
+        unsafe { ffi::sqlite3session_enable(self.raw, c_int::from(enabled)) };
+    }
+    /// Whether no changes have been recorded yet or not
+    pub fn is_empty(&self) -> bool {
+        unsafe { ffi::sqlite3session_isempty(self.raw) != 0 }
+    }
+
+    /// Creates a changeset with all recorded changes
+    pub fn changeset(&self) -> Result<Vec<u8>, Error> {
+        let (mut len, mut data) = (0, ptr::null_mut());
+        let retval = unsafe { ffi::sqlite3session_changeset(self.raw, &mut len, &mut data) };
+        unsafe { take_buffer(retval, len, data) }
+    }
+    /// Creates a patchset with all recorded changes
+    ///
+    /// # Note
+    /// A patchset is a more compact changeset which omits the old values of updated and deleted rows except for the
+    /// primary key. Consequently, it cannot be inverted, and fewer conflicts are detected when it is applied.
+    pub fn patchset(&self) -> Result<Vec<u8>, Error> {
+        let (mut len, mut data) = (0, ptr::null_mut());
+        let retval = unsafe { ffi::sqlite3session_patchset(self.raw, &mut len, &mut data) };
+        unsafe { take_buffer(retval, len, data) }
+    }
+}
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        unsafe { ffi::sqlite3session_delete(self.raw) };
+    }
+}
+
+/// A single change within a changeset or patchset
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The table name
+    pub table: String,
+    /// The operation
+    pub op: UpdateOp,
+    /// Whether the change has been made indirectly (e.g. by a trigger or a foreign key action) or not
+    pub indirect: bool,
+    /// For every column, whether it is part of the primary key or not
+    pub primary_key: Vec<bool>,
+    /// The values of the row before the change, or an empty vector for inserts
+    ///
+    /// # Note
+    /// Columns that are not part of the change (e.g. unchanged columns of updates) are `None`.
+    pub old: Vec<Option<SqliteType>>,
+    /// The values of the row after the change, or an empty vector for deletes
+    ///
+    /// # Note
+    /// Columns that are not part of the change (e.g. unchanged columns of updates) are `None`.
+    pub new: Vec<Option<SqliteType>>,
+}
+impl Change {
+    /// Reads the current change from the given iterator
+    ///
+    /// # Safety
+    /// `iter` must point to a valid changeset iterator which points to a change.
+    unsafe fn read(iter: *mut ffi::sqlite3_changeset_iter) -> Result<Self, Error> {
+        // Get the operation
+        let (mut table, mut columns, mut op, mut indirect) = (ptr::null(), 0, 0, 0);
+        let retval = unsafe { ffi::sqlite3changeset_op(iter, &mut table, &mut columns, &mut op, &mut indirect) };
+        unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
+        let table = unsafe { ffiext::sqlite3_owned_str(table) }?.ok_or_else(|| err!("Missing changeset table name"))?;
+        let op = UpdateOp::from_raw(op).ok_or_else(|| err!("Unknown changeset operation: {op}"))?;
+
+        // Get the primary key columns
+        let (mut flags, mut count) = (ptr::null_mut(), 0);
+        let retval = unsafe { ffi::sqlite3changeset_pk(iter, &mut flags, &mut count) };
+        unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
+        let count = usize::try_from(count).map_err(|e| err!(with: e, "Invalid changeset column count"))?;
+        let primary_key = match flags.is_null() {
+            true => Vec::new(),
+            false => unsafe { std::slice::from_raw_parts(flags, count) }.iter().map(|flag| *flag != 0).collect(),
+        };
+
+        // Get the values
+        // Note: Old values are only available for updates and deletes, and new values only for inserts and updates
+        let old = match op {
+            UpdateOp::Insert => Vec::new(),
+            _ => unsafe { read_values(iter, columns, ffi::sqlite3changeset_old) }?,
+        };
+        let new = match op {
+            UpdateOp::Delete => Vec::new(),
+            _ => unsafe { read_values(iter, columns, ffi::sqlite3changeset_new) }?,
+        };
+        Ok(Self { table, op, indirect: indirect != 0, primary_key, old, new })
+    }
+}
+
+/// An iterator over the changes within a changeset or patchset
+#[derive(Debug)]
+pub struct ChangesetIter<'a> {
+    /// The iterator handle
+    raw: PointerMut<ffi::sqlite3_changeset_iter>,
+    /// Whether the iterator is exhausted or not
+    done: bool,
+    /// The underlying changeset
+    _changeset: PhantomData<&'a [u8]>,
+}
+impl Iterator for ChangesetIter<'_> {
+    type Item = Result<Change, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        // Advance the iterator
+        let retval = unsafe { ffi::sqlite3changeset_next(self.raw.as_ptr()) };
+        match retval {
+            ffi::SQLITE_ROW => Some(unsafe { Change::read(self.raw.as_ptr()) }),
+            ffi::SQLITE_DONE => {
+                self.done = true;
+                None
+            }
+            _ => {
+                // Note: A corrupt changeset cannot be iterated any further
+                self.done = true;
+                Some(Err(unsafe { ffiext::sqlite3_last_error(retval, ptr::null_mut()) }))
+            }
+        }
+    }
+}
+
+/// The kind of a conflict that occurred while applying a changeset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictKind {
+    /// The row to update or delete exists, but its values do not match the old values of the change
+    Data,
+    /// The row to update or delete does not exist
+    NotFound,
+    /// The row to insert already exists
+    Conflict,
+    /// The change violates a `UNIQUE`, `CHECK` or `NOT NULL` constraint
+    Constraint,
+    /// The changes violate foreign key constraints
+    ForeignKey,
+}
+impl ConflictKind {
+    /// Maps a raw `SQLITE_CHANGESET_*` conflict kind
+    const fn from_raw(kind: c_int) -> Option<Self> {
+        match kind {
+            ffi::SQLITE_CHANGESET_DATA => Some(Self::Data),
+            ffi::SQLITE_CHANGESET_NOTFOUND => Some(Self::NotFound),
+            ffi::SQLITE_CHANGESET_CONFLICT => Some(Self::Conflict),
+            ffi::SQLITE_CHANGESET_CONSTRAINT => Some(Self::Constraint),
+            ffi::SQLITE_CHANGESET_FOREIGN_KEY => Some(Self::ForeignKey),
+            _ => None,
+        }
+    }
+}
+
+/// A conflict that occurred while applying a changeset
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// The kind of the conflict
+    pub kind: ConflictKind,
+    /// The change that caused the conflict, or `None` for [`ConflictKind::ForeignKey`] conflicts
+    pub change: Option<Change>,
+    /// The current values of the conflicting row for [`ConflictKind::Data`] and [`ConflictKind::Conflict`] conflicts,
+    /// or an empty vector otherwise
+    pub current: Vec<Option<SqliteType>>,
+    /// The amount of foreign key violations for [`ConflictKind::ForeignKey`] conflicts, or `0` otherwise
+    pub foreign_key_violations: usize,
+}
+impl Conflict {
+    /// Reads the conflict from the given iterator
+    ///
+    /// # Safety
+    /// `iter` must point to a valid changeset iterator that has been passed to a conflict handler with `kind`.
+    unsafe fn read(kind: ConflictKind, iter: *mut ffi::sqlite3_changeset_iter) -> Result<Self, Error> {
+        // Note: For foreign key conflicts, only the amount of violations is available
+        let true = kind != ConflictKind::ForeignKey else {
+            let mut violations = 0;
+            let retval = unsafe { ffi::sqlite3changeset_fk_conflicts(iter, &mut violations) };
+            unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
+            let violations = usize::try_from(violations).unwrap_or(0);
+            return Ok(Self { kind, change: None, current: Vec::new(), foreign_key_violations: violations });
+        };
+
+        // Get the change and the conflicting row if any
+        let change = unsafe { Change::read(iter) }?;
+        let current = match kind {
+            ConflictKind::Data | ConflictKind::Conflict => {
+                let columns = c_int::try_from(change.primary_key.len()).unwrap_or(0);
+                unsafe { read_values(iter, columns, ffi::sqlite3changeset_conflict) }?
+            }
+            _ => Vec::new(),
+        };
+        Ok(Self { kind, change: Some(change), current, foreign_key_violations: 0 })
+    }
+}
+
+/// The action to resolve a conflict that occurred while applying a changeset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictAction {
+    /// Skips the conflicting change
+    Omit,
+    /// Replaces the conflicting row with the change
+    ///
+    /// # Important
+    /// This is only valid for [`ConflictKind::Data`] and [`ConflictKind::Conflict`] conflicts; otherwise, applying the
+    /// changeset fails.
+    Replace,
+    /// Aborts and rolls back all changes
+    Abort,
+}
+impl ConflictAction {
+    /// The raw `SQLITE_CHANGESET_*` action
+    const fn into_raw(self) -> c_int {
+        match self {
+            Self::Omit => ffi::SQLITE_CHANGESET_OMIT,
+            Self::Replace => ffi::SQLITE_CHANGESET_REPLACE,
+            Self::Abort => ffi::SQLITE_CHANGESET_ABORT,
+        }
+    }
+}
+
+/// Iterates over the changes within the given changeset or patchset
+pub fn iter(changeset: &[u8]) -> Result<ChangesetIter<'_>, Error> {
+    let len = c_int::try_from(changeset.len()).map_err(|e| err!(with: e, "Changeset is too large"))?;
+
+    // Start the iterator
+    // Note: SQLite does not modify the changeset, even though the pointer is not const
+    let mut raw = ptr::null_mut();
+    let retval = unsafe { ffi::sqlite3changeset_start(&mut raw, len, changeset.as_ptr() as *mut c_void) };
+    unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
+    let raw = PointerMut::new(raw, ffi::sqlite3changeset_finalize);
+    Ok(ChangesetIter { raw, done: false, _changeset: PhantomData })
+}
+
+/// Inverts the given changeset, so that applying the result reverts the changes
+///
+/// # Note
+/// Patchsets cannot be inverted.
+pub fn invert(changeset: &[u8]) -> Result<Vec<u8>, Error> {
+    let len = c_int::try_from(changeset.len()).map_err(|e| err!(with: e, "Changeset is too large"))?;
+    let (mut out_len, mut out) = (0, ptr::null_mut());
+    let retval = unsafe { ffi::sqlite3changeset_invert(len, changeset.as_ptr() as _, &mut out_len, &mut out) };
+    unsafe { take_buffer(retval, out_len, out) }
+}
+
+/// Concatenates two changesets or two patchsets into a single one which has the same effect as applying both
+pub fn concat(first: &[u8], second: &[u8]) -> Result<Vec<u8>, Error> {
+    let first_len = c_int::try_from(first.len()).map_err(|e| err!(with: e, "Changeset is too large"))?;
+    let second_len = c_int::try_from(second.len()).map_err(|e| err!(with: e, "Changeset is too large"))?;
+
+    // Concatenate the changesets
+    // Note: SQLite does not modify the changesets, even though the pointers are not const
+    let (mut out_len, mut out) = (0, ptr::null_mut());
+    let retval = unsafe {
+        let (first, second) = (first.as_ptr() as *mut c_void, second.as_ptr() as *mut c_void);
+        ffi::sqlite3changeset_concat(first_len, first, second_len, second, &mut out_len, &mut out)
+    };
+    unsafe { take_buffer(retval, out_len, out) }
+}
+
+/// The context of a conflict handler
+struct ApplyContext<F> {
+    /// The conflict handler
+    handler: F,
+    /// An error that occurred within the conflict handler
+    error: Option<Error>,
+}
+
+/// Applies the given changeset or patchset to the `main` schema and resolves conflicts via `handler`
+pub(in crate::api) fn apply<F>(sqlite: &Sqlite, changeset: &[u8], handler: F) -> Result<(), Error>
+where
+    F: FnMut(&Conflict) -> ConflictAction,
+{
+    let len = c_int::try_from(changeset.len()).map_err(|e| err!(with: e, "Changeset is too large"))?;
+
+    // Apply the changeset
+    // Note: SQLite does not modify the changeset, even though the pointer is not const
+    let mut context = ApplyContext { handler, error: None };
+    let retval = unsafe {
+        ffi::sqlite3changeset_apply(
+            sqlite.raw.as_ptr(),
+            len,
+            changeset.as_ptr() as *mut c_void,
+            None,
+            Some(call_conflict::<F>),
+            &mut context as *mut ApplyContext<F> as *mut c_void,
+        )
+    };
+
+    // Prefer the error from the conflict handler if any
+    if let Some(error) = context.error {
+        return Err(error);
+    }
+    unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+}
+
+/// Calls the conflict handler
+unsafe extern "C" fn call_conflict<F>(
+    context: *mut c_void,
+    kind: c_int,
+    iter: *mut ffi::sqlite3_changeset_iter,
+) -> c_int
+where
+    F: FnMut(&Conflict) -> ConflictAction,
+{
+    let context = unsafe { &mut *(context as *mut ApplyContext<F>) };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Read the conflict
+        let kind = ConflictKind::from_raw(kind).ok_or_else(|| err!("Unknown changeset conflict kind: {kind}"))?;
+        let conflict = unsafe { Conflict::read(kind, iter) }?;
+        Ok((context.handler)(&conflict))
+    }));
+
+    // Note: If the handler panics or the conflict cannot be read, we abort to be on the safe side
+    match result {
+        Ok(Ok(action)) => action.into_raw(),
+        Ok(Err(error)) => {
+            context.error = Some(error);
+            ffi::SQLITE_CHANGESET_ABORT
+        }
+        Err(_) => {
+            context.error = Some(err!("Conflict handler panicked"));
+            ffi::SQLITE_CHANGESET_ABORT
+        }
+    }
+}
+
+/// Reads `columns` values from a changeset iterator via the given getter
+///
+/// # Safety
+/// `iter` must point to a valid changeset iterator for which `getter` may be called.
+unsafe fn read_values(
+    iter: *mut ffi::sqlite3_changeset_iter,
+    columns: c_int,
+    getter: ValueGetter,
+) -> Result<Vec<Option<SqliteType>>, Error> {
+    let mut values = Vec::new();
+    for column in 0..columns {
+        // Get the value
+        let mut value = ptr::null_mut();
+        let retval = unsafe { getter(iter, column, &mut value) };
+        unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
+
+        // Note: SQLite returns `NULL` for columns that are not part of the change
+        let value = match value.is_null() {
+            true => None,
+            false => Some(SqliteType::from(unsafe { ffiext::sqlite3_value_ref(value) }?)),
+        };
+        values.push(value);
+    }
+    Ok(values)
+}
+
+/// Copies a buffer that has been allocated by SQLite into a byte vector and frees it
+///
+/// # Safety
+/// If `retval` is `SQLITE_OK`, `data` must either be `NULL` or point to `len` bytes allocated by SQLite.
+unsafe fn take_buffer(retval: c_int, len: c_int, data: *mut c_void) -> Result<Vec<u8>, Error> {
+    // Note: SQLite may return `NULL` for empty buffers
+    let bytes = match (retval, data.is_null()) {
+        (ffi::SQLITE_OK, true) => Ok(Vec::new()),
+        (ffi::SQLITE_OK, false) => match usize::try_from(len) {
+            Ok(len) => Ok(unsafe { std::slice::from_raw_parts(data as *const u8, len) }.to_vec()),
+            Err(e) => Err(err!(with: e, "Invalid changeset size")),
+        },
+        _ => Err(unsafe { ffiext::sqlite3_last_error(retval, ptr::null_mut()) }),
+    };
+
+    // Note: `sqlite3_free` is a no-op for `NULL`
+    unsafe { ffi::sqlite3_free(data) };
+    bytes
+}
@@ -1,18 +1,46 @@
 //! An SQLite database handle
 
 use super::ffiext;
+use crate::api::backup::{self, Backup, BackupDatabase, Progress};
+use crate::api::blob::Blob;
+use crate::api::cache::{self, StatementCache};
+use crate::api::collation::{self, Collation};
 use crate::api::ffiext::PointerMut;
+use crate::api::function::{self, Aggregate, WindowFunction};
+#[cfg(feature = "preupdate-hook")]
+use crate::api::hooks::PreUpdate;
+use crate::api::hooks::{self, Hooks, UpdateOp};
 use crate::api::query::Query;
+use crate::api::savepoint::Savepoint;
+use crate::api::serialize;
+#[cfg(feature = "session")]
+use crate::api::session::{self, Conflict, ConflictAction, Session};
+use crate::api::trace::{self, TraceEvent, TraceMask};
+use crate::api::transaction::{Transaction, TransactionBehavior};
+use crate::api::types::{SqliteType, ValueRef};
+use crate::api::vtab::{self, VirtualTable};
 use crate::error::Error;
 use crate::{err, ffi};
+use std::cmp::Ordering;
 use std::ffi::CString;
 use std::ptr;
+use std::sync::{Arc, Mutex};
 
 /// An SQLite database handle
 #[derive(Debug)]
 pub struct Sqlite {
+    /// The prepared statement cache
+    ///
+    /// # Note
+    /// This field is declared before the database handle, so that cached statements are finalized first on drop
+    pub(in crate::api) cache: Arc<Mutex<StatementCache>>,
     /// The database handle
     pub(in crate::api) raw: PointerMut<ffi::sqlite3>,
+    /// Boxed callbacks that are registered with SQLite but not owned by it
+    ///
+    /// # Note
+    /// This field is declared after the database handle, so that the callbacks are dropped after the database is closed
+    pub(in crate::api) hooks: Mutex<Hooks>,
 }
 impl Sqlite {
     /// Opens or creates an SQLite 3 database for reading and writing
@@ -23,6 +51,12 @@ impl Sqlite {
     pub fn uri(uri: &str) -> Result<Self, Error> {
         Self::raw(uri, ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_URI)
     }
+    /// Opens an in-memory, read-only copy of a serialized database (e.g. a database embedded via `include_bytes!`)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let sqlite = Self::raw(":memory:", ffi::SQLITE_OPEN_READWRITE)?;
+        sqlite.deserialize("main", bytes, true)?;
+        Ok(sqlite)
+    }
     /// Opens an SQLite database at the given location with the given flags
     ///
     /// # Important
@@ -38,25 +72,79 @@ impl Sqlite {
         let retval = unsafe { ffi::sqlite3_open_v2(path.as_ptr(), &mut database, flags, ptr::null()) };
         unsafe { ffiext::sqlite3_check_result(retval, ptr::null_mut()) }?;
 
-        // Init self
+        // Init self and enable extended result codes
         let database = PointerMut::new(database, ffi::sqlite3_close_v2);
-        Ok(Self { raw: database })
+        let retval = unsafe { ffi::sqlite3_extended_result_codes(database.as_ptr(), 1) };
+        unsafe { ffiext::sqlite3_check_result(retval, database.as_ptr()) }?;
+        Ok(Self { cache: StatementCache::new(cache::DEFAULT_CAPACITY), raw: database, hooks: Mutex::default() })
     }
 
     /// Creates a new query from a **single** SQL statement
     pub fn query<'a>(&'a self, query: &str) -> Result<Query<'a>, Error> {
+        let statement = self.prepare(query, 0)?;
+        Ok(Query { sqlite: self, raw: statement })
+    }
+    /// Creates a new query from a **single** SQL statement using the prepared statement cache
+    ///
+    /// # Note
+    /// If the cache contains a statement with the same SQL text, it is reused instead of being prepared again. Once
+    /// the returned query or the resulting answer or row is dropped, the statement is reset, its bindings are cleared
+    /// and it is put back into the cache.
+    pub fn query_cached<'a>(&'a self, query: &str) -> Result<Query<'a>, Error> {
+        // Take the statement out of the cache or prepare a new one
+        let (statement, generation) = {
+            let mut cache = StatementCache::lock(&self.cache);
+            (cache.take(query), cache.generation())
+        };
+        let statement = match statement {
+            Some(statement) => statement,
+            None => self.prepare(query, ffi::SQLITE_PREPARE_PERSISTENT as _)?,
+        };
+
+        // Put the statement back into the cache once it is dropped
+        let (cache, query) = (Arc::clone(&self.cache), query.to_string());
+        let recycler = Box::new(move |statement: PointerMut<ffi::sqlite3_stmt>| {
+            // Note: `sqlite3_reset` only repeats the error of the last step if any, and `sqlite3_clear_bindings` always
+            //  succeeds
+            unsafe { ffi::sqlite3_reset(statement.as_ptr()) };
+            unsafe { ffi::sqlite3_clear_bindings(statement.as_ptr()) };
+            StatementCache::lock(&cache).put(query, statement, generation);
+        });
+        Ok(Query { sqlite: self, raw: statement.with_recycler(recycler) })
+    }
+    /// Prepares a **single** SQL statement with the given `SQLITE_PREPARE_*` flags
+    fn prepare(&self, query: &str, flags: std::ffi::c_uint) -> Result<PointerMut<ffi::sqlite3_stmt>, Error> {
         // Prepare query and statement pointer
         let query = CString::new(query).map_err(|e| err!(with: e, "Invalid database query"))?;
         let mut statement = ptr::null_mut();
 
         // Prepare statement and check result code
-        let retval =
-            unsafe { ffi::sqlite3_prepare_v2(self.raw.as_ptr(), query.as_ptr(), -1, &mut statement, ptr::null_mut()) };
+        let retval = unsafe {
+            ffi::sqlite3_prepare_v3(self.raw.as_ptr(), query.as_ptr(), -1, flags, &mut statement, ptr::null_mut())
+        };
         unsafe { ffiext::sqlite3_check_result(retval, self.raw.as_ptr()) }?;
 
-        // Init query
-        let statement = PointerMut::new(statement, ffi::sqlite3_finalize);
-        Ok(Query { sqlite: self, raw: statement })
+        // Init statement
+        // Note: `statement` is `NULL` if the query is empty or only contains a comment
+        let false = statement.is_null() else {
+            return Err(err!("Database query does not contain an SQL statement"));
+        };
+        Ok(PointerMut::new(statement, ffi::sqlite3_finalize))
+    }
+
+    /// Sets the capacity of the prepared statement cache (defaults to `256`)
+    ///
+    /// # Note
+    /// A capacity of `0` effectively disables the cache.
+    pub fn set_statement_cache_capacity(&self, capacity: usize) {
+        StatementCache::lock(&self.cache).set_capacity(capacity);
+    }
+    /// Discards all statements from the prepared statement cache (e.g. after schema changes)
+    ///
+    /// # Note
+    /// Statements that are currently in use are discarded once they are dropped.
+    pub fn flush_statement_cache(&self) {
+        StatementCache::lock(&self.cache).flush();
     }
 
     /// Executes one or more SQL queries
@@ -70,6 +158,309 @@ impl Sqlite {
         // Apparently, the query was successful
         Ok(())
     }
+
+    /// Begins a new transaction with the given behavior that is rolled back on drop unless it is committed
+    ///
+    /// # Important
+    /// Transactions cannot be nested; if a transaction is already active, this function returns an error. Use
+    /// [`Sqlite::savepoint`] for nested transactions.
+    pub fn transaction(&self, behavior: TransactionBehavior) -> Result<Transaction<'_>, Error> {
+        Transaction::begin(self, behavior)
+    }
+
+    /// Creates a new savepoint that is rolled back to on drop unless it is released
+    ///
+    /// # Note
+    /// If no transaction is active, the savepoint starts a new transaction which is committed once the savepoint is
+    /// released.
+    pub fn savepoint(&self) -> Result<Savepoint<'_>, Error> {
+        Savepoint::begin(self)
+    }
+
+    /// Registers or replaces a scalar SQL function
+    ///
+    /// # Note
+    /// `n_args` is the amount of arguments the function accepts, or `-1` for any amount of arguments. `flags` can be a
+    /// combination of `SQLITE_DETERMINISTIC`, `SQLITE_DIRECTONLY`, `SQLITE_INNOCUOUS` and `SQLITE_SUBTYPE`; the text
+    /// encoding is always UTF-8. If the function panics, the panic is caught and returned as SQL error.
+    pub fn create_scalar_function<F>(
+        &self,
+        name: &str,
+        n_args: std::ffi::c_int,
+        flags: std::ffi::c_int,
+        function: F,
+    ) -> Result<(), Error>
+    where
+        F: Fn(&[ValueRef]) -> Result<SqliteType, Error> + Send + 'static,
+    {
+        function::create_scalar(self, name, n_args, flags, function)
+    }
+    /// Registers or replaces an aggregate SQL function
+    ///
+    /// # Note
+    /// `n_args` and `flags` behave like for [`Sqlite::create_scalar_function`].
+    pub fn create_aggregate_function<A>(
+        &self,
+        name: &str,
+        n_args: std::ffi::c_int,
+        flags: std::ffi::c_int,
+        aggregate: A,
+    ) -> Result<(), Error>
+    where
+        A: Aggregate + Send + 'static,
+    {
+        function::create_aggregate(self, name, n_args, flags, aggregate)
+    }
+    /// Registers or replaces an aggregate window SQL function
+    ///
+    /// # Note
+    /// `n_args` and `flags` behave like for [`Sqlite::create_scalar_function`]. Like any other aggregate, the function
+    /// can also be used without an `OVER` clause.
+    pub fn create_window_function<W>(
+        &self,
+        name: &str,
+        n_args: std::ffi::c_int,
+        flags: std::ffi::c_int,
+        window: W,
+    ) -> Result<(), Error>
+    where
+        W: WindowFunction + Send + 'static,
+    {
+        function::create_window(self, name, n_args, flags, window)
+    }
+    /// Removes the SQL function with the given name and amount of arguments
+    pub fn remove_function(&self, name: &str, n_args: std::ffi::c_int) -> Result<(), Error> {
+        function::remove(self, name, n_args)
+    }
+
+    /// Registers or replaces a collation
+    ///
+    /// # Note
+    /// If the collation is used by a statement that has not been finalized yet, it cannot be replaced. Invalid UTF-8 is
+    /// replaced with `U+FFFD` before it is passed to the collation. If the collation panics, the panic is caught and the
+    /// strings are treated as equal.
+    pub fn create_collation<F>(&self, name: &str, collation: F) -> Result<(), Error>
+    where
+        F: Fn(&str, &str) -> Ordering + Send + 'static,
+    {
+        collation::create(self, name, collation)
+    }
+    /// Registers a callback that is called if a statement needs a collation that is not registered yet
+    ///
+    /// # Note
+    /// If the callback returns a collation, it is registered under the requested name; otherwise the statement fails.
+    /// The callback replaces any previously registered callback.
+    pub fn on_collation_needed<F>(&self, callback: F) -> Result<(), Error>
+    where
+        F: Fn(&str) -> Option<Collation> + Send + 'static,
+    {
+        collation::on_needed(self, callback)
+    }
+    /// Removes the callback that is called if a statement needs a collation that is not registered yet
+    pub fn remove_collation_needed(&self) -> Result<(), Error> {
+        collation::remove_needed(self)
+    }
+
+    /// Registers or replaces a virtual table module that can be used via `CREATE VIRTUAL TABLE`
+    pub fn create_module<T>(&self, name: &str, aux: T::Aux) -> Result<(), Error>
+    where
+        T: VirtualTable + Send + 'static,
+    {
+        vtab::create_module::<T>(self, name, aux, false)
+    }
+    /// Registers or replaces an eponymous-only virtual table module (e.g. a table-valued function)
+    ///
+    /// # Note
+    /// Eponymous-only modules cannot be used via `CREATE VIRTUAL TABLE`, but are implicitly available as table with the
+    /// same name as the module. Hidden columns can be used as arguments of a table-valued function.
+    pub fn create_eponymous_module<T>(&self, name: &str, aux: T::Aux) -> Result<(), Error>
+    where
+        T: VirtualTable + Send + 'static,
+    {
+        vtab::create_module::<T>(self, name, aux, true)
+    }
+
+    /// Starts a new online backup of this database into `destination`, which can be stepped manually
+    pub fn backup<'a>(&'a self, destination: &'a Sqlite) -> Result<Backup<'a>, Error> {
+        Backup::new(self, destination)
+    }
+    /// Backs up this database into `destination` in steps of `pages_per_step` pages (or in one step if negative), and
+    /// calls `progress` after each step
+    ///
+    /// # Note
+    /// If the destination is a path, the database is opened or created. Busy or locked steps are retried according to
+    /// [`backup::RetryPolicy::default`]; use [`Sqlite::backup`] and [`Backup::with_retry_policy`] for other policies.
+    pub fn backup_to<'a, D, F>(&self, destination: D, pages_per_step: std::ffi::c_int, progress: F) -> Result<(), Error>
+    where
+        D: Into<BackupDatabase<'a>>,
+        F: FnMut(Progress),
+    {
+        let destination: BackupDatabase = destination.into();
+        destination.with_destination(|destination| backup::backup(self, destination, pages_per_step, progress))
+    }
+    /// Restores this database from the backup `source`, replacing its entire contents
+    ///
+    /// # Note
+    /// This behaves like [`Sqlite::backup_to`] with the roles of source and destination swapped. If the source is a
+    /// path, the database is opened read-only and must exist.
+    pub fn restore_from<'a, S, F>(&self, source: S, pages_per_step: std::ffi::c_int, progress: F) -> Result<(), Error>
+    where
+        S: Into<BackupDatabase<'a>>,
+        F: FnMut(Progress),
+    {
+        let source: BackupDatabase = source.into();
+        source.with_source(|source| backup::backup(source, self, pages_per_step, progress))
+    }
+
+    /// Serializes the given schema (usually `main`) into the bytes of an SQLite database file
+    pub fn serialize(&self, schema: &str) -> Result<Vec<u8>, Error> {
+        serialize::serialize(self, schema)
+    }
+    /// Replaces the given schema (usually `main`) with an in-memory copy of the given serialized database
+    ///
+    /// # Note
+    /// The previous contents of the schema are discarded, even if the schema refers to a database file. If `read_only`
+    /// is `false`, the in-memory copy can be modified and grows as needed. This fails if the schema is currently in use,
+    /// e.g. by an active transaction.
+    pub fn deserialize(&self, schema: &str, bytes: &[u8], read_only: bool) -> Result<(), Error> {
+        serialize::deserialize(self, schema, bytes, read_only)
+    }
+
+    /// Opens the BLOB in the given column and row for incremental I/O
+    ///
+    /// # Note
+    /// `database` is the schema name of the table, usually `main`. If `writable` is `false`, the BLOB can only be read.
+    pub fn open_blob(
+        &self,
+        database: &str,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        writable: bool,
+    ) -> Result<Blob<'_>, Error> {
+        Blob::open(self, database, table, column, rowid, writable)
+    }
+
+    /// Registers a callback that is called for every row that is inserted, updated or deleted, with the operation, the
+    /// schema name, the table name and the rowid
+    ///
+    /// # Note
+    /// The callback replaces any previously registered callback. It is not called for `WITHOUT ROWID` tables, for
+    /// changes by conflict resolution, and for truncating deletes. If the callback panics, the panic is caught and
+    /// ignored.
+    ///
+    /// # Important
+    /// The callback must not modify the database.
+    pub fn on_update<F>(&self, callback: F)
+    where
+        F: Fn(UpdateOp, &str, &str, i64) + Send + 'static,
+    {
+        hooks::on_update(self, Some(callback));
+    }
+    /// Removes the callback that is called for every row that is inserted, updated or deleted
+    pub fn remove_update_hook(&self) {
+        hooks::on_update::<fn(UpdateOp, &str, &str, i64)>(self, None);
+    }
+    /// Registers a callback that is called before a transaction is committed; if it returns `true`, the commit is
+    /// vetoed and turned into a rollback
+    ///
+    /// # Note
+    /// The callback replaces any previously registered callback. If the callback panics, the panic is caught and the
+    /// commit is vetoed.
+    ///
+    /// # Important
+    /// The callback must not modify the database.
+    pub fn on_commit<F>(&self, callback: F)
+    where
+        F: Fn() -> bool + Send + 'static,
+    {
+        hooks::on_commit(self, Some(callback));
+    }
+    /// Removes the callback that is called before a transaction is committed
+    pub fn remove_commit_hook(&self) {
+        hooks::on_commit::<fn() -> bool>(self, None);
+    }
+    /// Registers a callback that is called after a transaction has been rolled back
+    ///
+    /// # Note
+    /// The callback replaces any previously registered callback. It is not called if the database is closed with an
+    /// active transaction. If the callback panics, the panic is caught and ignored.
+    pub fn on_rollback<F>(&self, callback: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        hooks::on_rollback(self, Some(callback));
+    }
+    /// Removes the callback that is called after a transaction has been rolled back
+    pub fn remove_rollback_hook(&self) {
+        hooks::on_rollback::<fn()>(self, None);
+    }
+
+    /// Registers a callback that is called before every row is inserted, updated or deleted, with access to the old and
+    /// new values of the row
+    ///
+    /// # Note
+    /// The callback replaces any previously registered callback. It is not called for virtual tables and system
+    /// tables. If the callback panics, the panic is caught and ignored.
+    ///
+    /// # Important
+    /// The callback must not modify the database.
+    #[cfg(feature = "preupdate-hook")]
+    pub fn on_preupdate<F>(&self, callback: F)
+    where
+        F: Fn(&PreUpdate) + Send + 'static,
+    {
+        hooks::on_preupdate(self, Some(callback));
+    }
+    /// Removes the callback that is called before every row is inserted, updated or deleted
+    #[cfg(feature = "preupdate-hook")]
+    pub fn remove_preupdate_hook(&self) {
+        hooks::on_preupdate::<fn(&PreUpdate)>(self, None);
+    }
+
+    /// Starts a new session that records changes to the given schema (e.g. `main`) as changeset or patchset
+    ///
+    /// # Note
+    /// No tables are attached initially, so no changes are recorded until tables are attached to the session.
+    #[cfg(feature = "session")]
+    pub fn session(&self, schema: &str) -> Result<Session<'_>, Error> {
+        Session::new(self, schema)
+    }
+    /// Applies the given changeset or patchset to the `main` schema and resolves conflicts via `on_conflict`
+    ///
+    /// # Note
+    /// The changeset is applied atomically; if `on_conflict` returns [`ConflictAction::Abort`] or panics, all changes are
+    /// rolled back and an error is returned.
+    #[cfg(feature = "session")]
+    pub fn apply_changeset<F>(&self, changeset: &[u8], on_conflict: F) -> Result<(), Error>
+    where
+        F: FnMut(&Conflict) -> ConflictAction,
+    {
+        session::apply(self, changeset, on_conflict)
+    }
+
+    /// Registers a callback that is called for the trace events selected by `mask`
+    ///
+    /// # Note
+    /// The callback replaces any previously registered callback; an empty mask removes it. If the callback panics, the
+    /// panic is caught and ignored. With the `log`-feature enabled, `crate::api::trace::log` and
+    /// `crate::api::trace::log_slow` can be used as callbacks to forward the events to the `log` crate.
+    pub fn trace<F>(&self, mask: TraceMask, callback: F) -> Result<(), Error>
+    where
+        F: Fn(TraceEvent) + Send + 'static,
+    {
+        trace::trace(self, mask, Some(callback))
+    }
+    /// Removes the trace callback
+    pub fn remove_trace(&self) -> Result<(), Error> {
+        trace::trace::<fn(TraceEvent)>(self, TraceMask::NONE, None)
+    }
+
+    /// Whether a transaction is currently active or not (i.e. the database is not in autocommit mode)
+    pub fn in_transaction(&self) -> bool {
+        let autocommit = unsafe { ffi::sqlite3_get_autocommit(self.raw.as_ptr()) };
+        autocommit == 0
+    }
 }
 unsafe impl Send for Sqlite {
     // This struct is safely send because:
@@ -0,0 +1,198 @@
+//! Statement tracing and profiling
+
+use crate::api::ffiext;
+use crate::api::hooks::{self, Hooks};
+use crate::error::Error;
+use crate::{ffi, Sqlite};
+use std::borrow::Cow;
+use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+use std::ops::BitOr;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::time::Duration;
+
+/// A set of trace events to subscribe to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TraceMask {
+    /// The raw `SQLITE_TRACE_*` flags
+    bits: c_uint,
+}
+impl TraceMask {
+    /// No events
+    pub const NONE: Self = Self { bits: 0 };
+    /// [`TraceEvent::Stmt`] events
+    pub const STMT: Self = Self { bits: ffi::SQLITE_TRACE_STMT as c_uint };
+    /// [`TraceEvent::Profile`] events
+    pub const PROFILE: Self = Self { bits: ffi::SQLITE_TRACE_PROFILE as c_uint };
+    /// [`TraceEvent::Row`] events
+    pub const ROW: Self = Self { bits: ffi::SQLITE_TRACE_ROW as c_uint };
+    /// [`TraceEvent::Close`] events
+    pub const CLOSE: Self = Self { bits: ffi::SQLITE_TRACE_CLOSE as c_uint };
+    /// All events
+    pub const ALL: Self = Self { bits: Self::STMT.bits | Self::PROFILE.bits | Self::ROW.bits | Self::CLOSE.bits };
+
+    /// Whether the mask contains all events of `other` or not
+    pub const fn contains(self, other: Self) -> bool {
+        self.bits & other.bits == other.bits
+    }
+    /// Whether the mask is empty or not
+    pub const fn is_empty(self) -> bool {
+        self.bits == 0
+    }
+}
+impl BitOr for TraceMask {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self { bits: self.bits | rhs.bits }
+    }
+}
+
+/// A trace event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceEvent<'a> {
+    /// A statement starts running, with the SQL text and the bound parameters expanded
+    ///
+    /// # Note
+    /// For statements within triggers, the SQL text is a comment that names the trigger (e.g. `-- TRIGGER name`).
+    Stmt {
+        /// The expanded SQL text
+        sql: &'a str,
+    },
+    /// A statement has finished
+    Profile {
+        /// The SQL text of the statement
+        sql: &'a str,
+        /// The approximate wall-clock time the statement took
+        ///
+        /// # Note
+        /// SQLite reports the duration in nanoseconds, but the actual resolution depends on the OS clock and is often
+        /// only milliseconds.
+        duration: Duration,
+    },
+    /// A statement has produced a result row
+    Row {
+        /// The SQL text of the statement
+        sql: &'a str,
+    },
+    /// The database connection is closed
+    Close,
+}
+
+/// Registers or removes the trace callback
+pub(in crate::api) fn trace<F>(sqlite: &Sqlite, mask: TraceMask, callback: Option<F>) -> Result<(), Error>
+where
+    F: Fn(TraceEvent) + Send + 'static,
+{
+    // Note: An empty mask disables tracing, so there is no need to keep the callback alive
+    let callback = callback.filter(|_| !mask.is_empty());
+    let (data, callback) = callback.map(hooks::share).unzip();
+    let trampoline = callback.as_ref().map(|_| call_trace::<F> as _);
+
+    // Register the callback
+    Hooks::register(
+        sqlite,
+        |hooks| &mut hooks.trace,
+        callback,
+        || {
+            let data = data.unwrap_or(ptr::null_mut());
+            let retval = unsafe { ffi::sqlite3_trace_v2(sqlite.raw.as_ptr(), mask.bits, trampoline, data) };
+            unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+        },
+    )
+}
+
+/// Calls the trace callback
+unsafe extern "C" fn call_trace<F>(event: c_uint, data: *mut c_void, p: *mut c_void, x: *mut c_void) -> c_int
+where
+    F: Fn(TraceEvent),
+{
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let callback = unsafe { hooks::retain::<F>(data) };
+        match event as c_int {
+            ffi::SQLITE_TRACE_STMT => {
+                // Note: For trigger comments, `x` is the comment; otherwise, we expand the statement
+                let unexpanded = unsafe { lossy_str(x as *const c_char) };
+                let sql = match unexpanded.starts_with("--") {
+                    true => unexpanded,
+                    false => unsafe { expanded_sql(p as *mut ffi::sqlite3_stmt) }.unwrap_or(unexpanded),
+                };
+                callback(TraceEvent::Stmt { sql: &sql });
+            }
+            ffi::SQLITE_TRACE_PROFILE => {
+                let sql = unsafe { lossy_str(ffi::sqlite3_sql(p as *mut ffi::sqlite3_stmt)) };
+                let nanos = unsafe { *(x as *const i64) };
+                let duration = Duration::from_nanos(u64::try_from(nanos).unwrap_or(0));
+                callback(TraceEvent::Profile { sql: &sql, duration });
+            }
+            ffi::SQLITE_TRACE_ROW => {
+                let sql = unsafe { lossy_str(ffi::sqlite3_sql(p as *mut ffi::sqlite3_stmt)) };
+                callback(TraceEvent::Row { sql: &sql });
+            }
+            ffi::SQLITE_TRACE_CLOSE => callback(TraceEvent::Close),
+            _ => (),
+        }
+    }));
+
+    // Note: The return value is reserved and must be zero
+    0
+}
+
+/// Copies a C string lossily, or returns an empty string for `NULL`
+///
+/// # Safety
+/// `chars` must either be `NULL` or point to a valid, `NUL`-terminated C string.
+unsafe fn lossy_str<'a>(chars: *const c_char) -> Cow<'a, str> {
+    match chars.is_null() {
+        true => Cow::Borrowed(""),
+        false => unsafe { CStr::from_ptr(chars) }.to_string_lossy(),
+    }
+}
+
+/// Gets the SQL text of the given statement with the bound parameters expanded, or `None` if SQLite is out of memory
+///
+/// # Safety
+/// `stmt` must point to a valid statement.
+unsafe fn expanded_sql<'a>(stmt: *mut ffi::sqlite3_stmt) -> Option<Cow<'a, str>> {
+    let chars = unsafe { ffi::sqlite3_expanded_sql(stmt) };
+    let false = chars.is_null() else {
+        return None;
+    };
+
+    // Copy the string
+    // Note: The string is allocated by SQLite and must be freed with `sqlite3_free`
+    let sql = unsafe { CStr::from_ptr(chars) }.to_string_lossy().into_owned();
+    unsafe { ffi::sqlite3_free(chars as *mut c_void) };
+    Some(Cow::Owned(sql))
+}
+
+/// Forwards a trace event to the `log` crate
+///
+/// # Note
+/// Statements and profiles are logged with level `debug`, rows with level `trace`. To subscribe to all events, use
+/// `sqlite.trace(TraceMask::ALL, trace::log)`.
+#[cfg(feature = "log")]
+pub fn log(event: TraceEvent) {
+    match event {
+        TraceEvent::Stmt { sql } => log::debug!("Running statement: {sql}"),
+        TraceEvent::Profile { sql, duration } => log::debug!("Finished statement after {duration:?}: {sql}"),
+        TraceEvent::Row { sql } => log::trace!("Statement produced row: {sql}"),
+        TraceEvent::Close => log::debug!("Closed database connection"),
+    }
+}
+
+/// Creates a trace callback that logs statements which take at least `threshold` as warning via the `log` crate
+///
+/// # Note
+/// Only [`TraceEvent::Profile`] events are evaluated, so a slow-query log can be enabled via
+/// `sqlite.trace(TraceMask::PROFILE, trace::log_slow(threshold))`.
+#[cfg(feature = "log")]
+pub fn log_slow(threshold: Duration) -> impl Fn(TraceEvent) + Send + 'static {
+    move |event| {
+        if let TraceEvent::Profile { sql, duration } = event {
+            if duration >= threshold {
+                log::warn!("Slow statement took {duration:?}: {sql}");
+            }
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! An SQLite transaction
+
+use crate::api::query::Query;
+use crate::api::savepoint::Savepoint;
+use crate::error::Error;
+use crate::{err, Sqlite};
+
+/// The behavior of a transaction (see <https://www.sqlite.org/lang_transaction.html>)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TransactionBehavior {
+    /// The transaction does not actually start until the database is first accessed
+    #[default]
+    Deferred,
+    /// A write transaction is started immediately, without waiting for a write statement
+    Immediate,
+    /// Like [`TransactionBehavior::Immediate`], but also prevents other connections from reading in most journal modes
+    Exclusive,
+}
+impl TransactionBehavior {
+    /// The `BEGIN` statement for the transaction behavior
+    const fn begin(self) -> &'static str {
+        match self {
+            Self::Deferred => "BEGIN DEFERRED",
+            Self::Immediate => "BEGIN IMMEDIATE",
+            Self::Exclusive => "BEGIN EXCLUSIVE",
+        }
+    }
+}
+
+/// An SQLite transaction which is rolled back on drop unless it has been committed
+#[derive(Debug)]
+pub struct Transaction<'db> {
+    /// The database
+    pub(in crate::api) sqlite: &'db Sqlite,
+    /// Whether the transaction has already been committed or rolled back
+    finished: bool,
+}
+impl<'db> Transaction<'db> {
+    /// Begins a new transaction with the given behavior
+    pub(in crate::api) fn begin(sqlite: &'db Sqlite, behavior: TransactionBehavior) -> Result<Self, Error> {
+        // Refuse to nest transactions
+        if sqlite.in_transaction() {
+            return Err(err!("Cannot begin a transaction within another transaction"));
+        }
+
+        // Begin the transaction
+        sqlite.execute(behavior.begin())?;
+        Ok(Self { sqlite, finished: false })
+    }
+
+    /// Creates a new savepoint within the transaction
+    pub fn savepoint(&mut self) -> Result<Savepoint<'_>, Error> {
+        Savepoint::begin(self.sqlite)
+    }
+
+    /// Creates a new query from a **single** SQL statement within the transaction
+    pub fn query<'a>(&'a self, query: &str) -> Result<Query<'a>, Error> {
+        self.sqlite.query(query)
+    }
+
+    /// Executes one or more SQL queries within the transaction
+    pub fn execute(&self, query: &str) -> Result<(), Error> {
+        self.sqlite.execute(query)
+    }
+
+    /// Commits the transaction
+    pub fn commit(mut self) -> Result<(), Error> {
+        // Note: If the commit fails, the transaction is still rolled back on drop
+        self.sqlite.execute("COMMIT")?;
+        self.finished = true;
+        Ok(())
+    }
+
+    /// Rolls the transaction back
+    pub fn rollback(mut self) -> Result<(), Error> {
+        self.finished = true;
+        self.sqlite.execute("ROLLBACK")
+    }
+}
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        // Roll back the transaction if it has not been finished yet
+        // Note: The transaction might have been rolled back automatically by SQLite already, so we check first
+        if !self.finished && self.sqlite.in_transaction() {
+            let _ = self.sqlite.execute("ROLLBACK");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Tuple implementations for positional binding and reading
+
+use crate::api::query::{BindAll, Query};
+use crate::api::row::{FromRow, Row};
+use crate::api::types::SqliteType;
+use crate::err;
+use crate::error::Error;
+
+/// Implements [`FromRow`] and [`BindAll`] for a tuple
+macro_rules! impl_tuple {
+    ($len:literal => $($type:ident $index:tt),+) => {
+        impl<$($type),+> FromRow for ($($type,)+)
+        where
+            $(
+                SqliteType: TryInto<$type>,
+                <SqliteType as TryInto<$type>>::Error: std::error::Error + Send + 'static,
+            )+
+        {
+            fn from_row(row: &Row) -> Result<Self, Error> {
+                // Ensure the amount of columns matches the tuple arity
+                let true = row.len() == $len else {
+                    return Err(err!("Cannot read row with {} columns into tuple with {} elements", row.len(), $len));
+                };
+
+                // Read the columns
+                Ok(($(row.read::<$type>($index)?,)+))
+            }
+        }
+        impl<$($type),+> BindAll for ($($type,)+)
+        where
+            $(
+                SqliteType: TryFrom<$type>,
+                <SqliteType as TryFrom<$type>>::Error: std::error::Error + Send + 'static,
+            )+
+        {
+            fn bind_all(self, query: Query) -> Result<Query, Error> {
+                // Note: Parameter indices start with `1`
+                $(let query = query.bind($index + 1, self.$index)?;)+
+                Ok(query)
+            }
+        }
+    };
+}
+impl_tuple!(1 => A 0);
+impl_tuple!(2 => A 0, B 1);
+impl_tuple!(3 => A 0, B 1, C 2);
+impl_tuple!(4 => A 0, B 1, C 2, D 3);
+impl_tuple!(5 => A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(7 => A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(8 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_tuple!(9 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_tuple!(10 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_tuple!(11 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_tuple!(12 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+impl_tuple!(13 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12);
+impl_tuple!(14 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13);
+impl_tuple!(15 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14);
+impl_tuple!(16 => A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15);
@@ -19,6 +19,104 @@ pub enum SqliteType {
     Blob(Vec<u8>),
 }
 
+/// A borrowed reference to an SQLite value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueRef<'a> {
+    /// NULL
+    Null,
+    /// INTEGER
+    Integer(i64),
+    /// REAL
+    Real(f64),
+    /// TEXT
+    Text(&'a str),
+    /// BLOB
+    Blob(&'a [u8]),
+}
+/// A borrowed array that can be bound as `carray()` table (see <https://www.sqlite.org/carray.html>)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArrayRef<'a> {
+    /// An array of INTEGERs
+    Integer(&'a [i64]),
+    /// An array of REALs
+    Real(&'a [f64]),
+    /// An array of TEXTs
+    Text(&'a [String]),
+    /// An array of BLOBs
+    Blob(&'a [Vec<u8>]),
+}
+impl<'a> From<&'a [i64]> for ArrayRef<'a> {
+    fn from(values: &'a [i64]) -> Self {
+        Self::Integer(values)
+    }
+}
+impl<'a> From<&'a [f64]> for ArrayRef<'a> {
+    fn from(values: &'a [f64]) -> Self {
+        Self::Real(values)
+    }
+}
+impl<'a> From<&'a [String]> for ArrayRef<'a> {
+    fn from(values: &'a [String]) -> Self {
+        Self::Text(values)
+    }
+}
+impl<'a> From<&'a [Vec<u8>]> for ArrayRef<'a> {
+    fn from(values: &'a [Vec<u8>]) -> Self {
+        Self::Blob(values)
+    }
+}
+impl<'a, T> From<&'a Vec<T>> for ArrayRef<'a>
+where
+    ArrayRef<'a>: From<&'a [T]>,
+{
+    fn from(values: &'a Vec<T>) -> Self {
+        Self::from(values.as_slice())
+    }
+}
+
+impl From<i64> for ValueRef<'_> {
+    fn from(value: i64) -> Self {
+        Self::Integer(value)
+    }
+}
+impl From<f64> for ValueRef<'_> {
+    fn from(value: f64) -> Self {
+        Self::Real(value)
+    }
+}
+impl<'a> From<&'a str> for ValueRef<'a> {
+    fn from(value: &'a str) -> Self {
+        Self::Text(value)
+    }
+}
+impl<'a> From<&'a [u8]> for ValueRef<'a> {
+    fn from(value: &'a [u8]) -> Self {
+        Self::Blob(value)
+    }
+}
+impl From<ValueRef<'_>> for SqliteType {
+    fn from(value: ValueRef) -> Self {
+        match value {
+            ValueRef::Null => Self::Null,
+            ValueRef::Integer(value) => Self::Integer(value),
+            ValueRef::Real(value) => Self::Real(value),
+            ValueRef::Text(value) => Self::Text(value.to_string()),
+            ValueRef::Blob(value) => Self::Blob(value.to_vec()),
+        }
+    }
+}
+impl<'a> From<&'a SqliteType> for ValueRef<'a> {
+    fn from(value: &'a SqliteType) -> Self {
+        match value {
+            SqliteType::Null => Self::Null,
+            SqliteType::Integer(value) => Self::Integer(*value),
+            SqliteType::Real(value) => Self::Real(*value),
+            SqliteType::Text(value) => Self::Text(value),
+            SqliteType::Blob(value) => Self::Blob(value),
+        }
+    }
+}
+
 // Byte array conversions
 impl<const LEN: usize> TryInto<[u8; LEN]> for SqliteType {
     type Error = Error;
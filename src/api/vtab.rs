@@ -0,0 +1,749 @@
+//! Application-defined virtual tables
+
+use crate::api::ffiext;
+use crate::api::function::drop_boxed;
+use crate::api::types::{SqliteType, ValueRef};
+use crate::error::Error;
+use crate::{err, ffi, Sqlite};
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+/// A virtual table implementation
+///
+/// # Note
+/// Every table that uses the module is a separate instance of the implementing type, which is created via
+/// [`VirtualTable::create`] or [`VirtualTable::connect`]. The module-wide [`VirtualTable::Aux`] data is shared between
+/// all tables, and can e.g. be used to pass in-process data to the tables.
+pub trait VirtualTable: Sized {
+    /// The module-wide auxiliary data
+    type Aux: Send + 'static;
+    /// The cursor type to iterate over the table
+    type Cursor: VTabCursor + Send + 'static;
+    /// Whether the table is writable via [`VirtualTable::update`] or read-only
+    const WRITABLE: bool = false;
+
+    /// Connects to an existing table and returns its schema together with the table
+    ///
+    /// # Note
+    /// The schema must be a `CREATE TABLE` statement which declares the columns of the table; the table name is
+    /// ignored. `args` contains the module name, the database name and the table name, followed by the module
+    /// arguments (if any).
+    fn connect(aux: &Self::Aux, args: &[&str]) -> Result<(String, Self), Error>;
+    /// Creates a new table via `CREATE VIRTUAL TABLE` and returns its schema together with the table
+    ///
+    /// # Note
+    /// This function is never called for eponymous-only modules. The arguments behave like for
+    /// [`VirtualTable::connect`], which is called by default.
+    fn create(aux: &Self::Aux, args: &[&str]) -> Result<(String, Self), Error> {
+        Self::connect(aux, args)
+    }
+    /// Destroys a table via `DROP TABLE`, e.g. to release its backing store
+    ///
+    /// # Note
+    /// The table is disconnected (i.e. dropped) afterwards, unless this function returns an error. By default, nothing
+    /// else happens, so destroying a table behaves like disconnecting from it.
+    fn destroy(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Selects the best query plan for the given constraints and order
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), Error>;
+    /// Opens a new cursor
+    fn open(&self) -> Result<Self::Cursor, Error>;
+
+    /// Applies a change to the table and returns the rowid of the inserted row
+    ///
+    /// # Note
+    /// This function is only called if [`VirtualTable::WRITABLE`] is `true`; otherwise, SQLite rejects all changes to
+    /// the table. The returned rowid is ignored for deletes and updates.
+    fn update(&mut self, change: VTabChange) -> Result<i64, Error> {
+        let _ = change;
+        Err(err!("Virtual table is read-only"))
+    }
+}
+
+/// A cursor over a virtual table
+pub trait VTabCursor {
+    /// Starts a new search
+    ///
+    /// # Note
+    /// `idx_num` and `idx_str` are the values that have been set by [`VirtualTable::best_index`], and `args` contains
+    /// the right-hand side values of the constraints in the order of their assigned argument indices.
+    fn filter(&mut self, idx_num: i32, idx_str: Option<&str>, args: &[ValueRef]) -> Result<(), Error>;
+    /// Advances the cursor to the next row
+    fn next(&mut self) -> Result<(), Error>;
+    /// Whether the cursor has been advanced past the last row or not
+    fn eof(&self) -> bool;
+    /// Reads the given column of the current row
+    fn column(&self, column: usize) -> Result<SqliteType, Error>;
+    /// The rowid of the current row
+    fn rowid(&self) -> Result<i64, Error>;
+}
+
+/// A change to a writable virtual table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VTabChange<'a> {
+    /// Deletes the row with the given rowid
+    Delete {
+        /// The rowid of the row to delete
+        rowid: i64,
+    },
+    /// Inserts a new row
+    Insert {
+        /// The rowid of the new row, or `None` if the table should choose one
+        rowid: Option<i64>,
+        /// The column values of the new row
+        values: &'a [ValueRef<'a>],
+    },
+    /// Updates an existing row
+    Update {
+        /// The rowid of the row to update
+        old_rowid: i64,
+        /// The new rowid of the row (which is usually equal to the old rowid)
+        new_rowid: i64,
+        /// The new column values of the row
+        values: &'a [ValueRef<'a>],
+    },
+}
+
+/// A constraint operator (see <https://www.sqlite.org/c3ref/c_index_constraint_eq.html>)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstraintOp {
+    /// `=`
+    Eq,
+    /// `>`
+    Gt,
+    /// `<=`
+    Le,
+    /// `<`
+    Lt,
+    /// `>=`
+    Ge,
+    /// `MATCH`
+    Match,
+    /// `LIKE`
+    Like,
+    /// `GLOB`
+    Glob,
+    /// `REGEXP`
+    Regexp,
+    /// `!=` or `<>`
+    Ne,
+    /// `IS NOT`
+    IsNot,
+    /// `IS NOT NULL`
+    IsNotNull,
+    /// `IS NULL`
+    IsNull,
+    /// `IS`
+    Is,
+    /// `LIMIT`
+    Limit,
+    /// `OFFSET`
+    Offset,
+    /// Any other operator, including overloaded functions
+    Other(u8),
+}
+impl ConstraintOp {
+    /// Maps a raw `SQLITE_INDEX_CONSTRAINT_*` operator
+    const fn from_raw(op: u8) -> Self {
+        match op as c_int {
+            ffi::SQLITE_INDEX_CONSTRAINT_EQ => Self::Eq,
+            ffi::SQLITE_INDEX_CONSTRAINT_GT => Self::Gt,
+            ffi::SQLITE_INDEX_CONSTRAINT_LE => Self::Le,
+            ffi::SQLITE_INDEX_CONSTRAINT_LT => Self::Lt,
+            ffi::SQLITE_INDEX_CONSTRAINT_GE => Self::Ge,
+            ffi::SQLITE_INDEX_CONSTRAINT_MATCH => Self::Match,
+            ffi::SQLITE_INDEX_CONSTRAINT_LIKE => Self::Like,
+            ffi::SQLITE_INDEX_CONSTRAINT_GLOB => Self::Glob,
+            ffi::SQLITE_INDEX_CONSTRAINT_REGEXP => Self::Regexp,
+            ffi::SQLITE_INDEX_CONSTRAINT_NE => Self::Ne,
+            ffi::SQLITE_INDEX_CONSTRAINT_ISNOT => Self::IsNot,
+            ffi::SQLITE_INDEX_CONSTRAINT_ISNOTNULL => Self::IsNotNull,
+            ffi::SQLITE_INDEX_CONSTRAINT_ISNULL => Self::IsNull,
+            ffi::SQLITE_INDEX_CONSTRAINT_IS => Self::Is,
+            ffi::SQLITE_INDEX_CONSTRAINT_LIMIT => Self::Limit,
+            ffi::SQLITE_INDEX_CONSTRAINT_OFFSET => Self::Offset,
+            _ => Self::Other(op),
+        }
+    }
+}
+
+/// A `WHERE` clause constraint on a virtual table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndexConstraint {
+    /// The constrained column, or `-1` for the rowid
+    pub column: i32,
+    /// The constraint operator
+    pub op: ConstraintOp,
+    /// Whether the constraint can be used for the current plan or not
+    pub usable: bool,
+}
+
+/// An `ORDER BY` term on a virtual table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndexOrderBy {
+    /// The ordered column, or `-1` for the rowid
+    pub column: i32,
+    /// Whether the order is descending or not
+    pub desc: bool,
+}
+
+/// The query planner negotiation for a virtual table (see <https://www.sqlite.org/vtab.html#xbestindex>)
+#[derive(Debug)]
+pub struct IndexInfo<'a> {
+    /// The underlying index info
+    raw: &'a mut ffi::sqlite3_index_info,
+}
+impl IndexInfo<'_> {
+    /// The constraints of the `WHERE` clause
+    pub fn constraints(&self) -> Vec<IndexConstraint> {
+        let constraints = unsafe { raw_slice(self.raw.aConstraint, self.raw.nConstraint) };
+        let constraints = constraints.iter().map(|constraint| IndexConstraint {
+            column: constraint.iColumn,
+            op: ConstraintOp::from_raw(constraint.op),
+            usable: constraint.usable != 0,
+        });
+        constraints.collect()
+    }
+    /// The terms of the `ORDER BY` clause
+    pub fn order_by(&self) -> Vec<IndexOrderBy> {
+        let order_by = unsafe { raw_slice(self.raw.aOrderBy, self.raw.nOrderBy) };
+        let order_by = order_by.iter().map(|term| IndexOrderBy { column: term.iColumn, desc: term.desc != 0 });
+        order_by.collect()
+    }
+
+    /// Passes the right-hand side value of the constraint with the given index to [`VTabCursor::filter`]
+    ///
+    /// # Note
+    /// `argv_index` is the one-based position of the value within the filter arguments. If `omit` is `true`, SQLite
+    /// does not double-check the constraint.
+    pub fn use_constraint(&mut self, constraint: usize, argv_index: u16, omit: bool) -> Result<(), Error> {
+        // Validate the arguments
+        if argv_index == 0 {
+            return Err(err!("Argument indices are one-based"));
+        }
+        let usage = unsafe { raw_slice_mut(self.raw.aConstraintUsage, self.raw.nConstraint) };
+        let usage = usage.get_mut(constraint).ok_or_else(|| err!("Constraint index is out of bounds"))?;
+
+        // Set the usage
+        usage.argvIndex = c_int::from(argv_index);
+        usage.omit = u8::from(omit);
+        Ok(())
+    }
+    /// Sets the plan number that is passed to [`VTabCursor::filter`]
+    pub fn set_idx_num(&mut self, idx_num: i32) {
+        self.raw.idxNum = idx_num;
+    }
+    /// Sets the plan string that is passed to [`VTabCursor::filter`]
+    pub fn set_idx_str(&mut self, idx_str: &str) -> Result<(), Error> {
+        // Allocate the new string
+        let chars = ffiext::sqlite3_malloc_str(idx_str);
+        let false = chars.is_null() else {
+            // SQLite is out of memory
+            return Err(err!("Failed to allocate plan string"));
+        };
+
+        // Free the previous string if necessary and set the new string
+        // Note: SQLite frees the string with `sqlite3_free` if `needToFreeIdxStr` is set
+        if self.raw.needToFreeIdxStr != 0 {
+            unsafe { ffi::sqlite3_free(self.raw.idxStr as *mut c_void) };
+        }
+        self.raw.idxStr = chars;
+        self.raw.needToFreeIdxStr = 1;
+        Ok(())
+    }
+    /// Declares that the cursor returns the rows in the requested `ORDER BY` order
+    pub fn set_order_by_consumed(&mut self, consumed: bool) {
+        self.raw.orderByConsumed = c_int::from(consumed);
+    }
+    /// Sets the estimated cost of the plan
+    pub fn set_estimated_cost(&mut self, cost: f64) {
+        self.raw.estimatedCost = cost;
+    }
+    /// Sets the estimated amount of rows returned by the plan
+    pub fn set_estimated_rows(&mut self, rows: i64) {
+        self.raw.estimatedRows = rows;
+    }
+    /// Declares that the plan returns at most one row
+    pub fn set_unique(&mut self, unique: bool) {
+        match unique {
+            true => self.raw.idxFlags |= ffi::SQLITE_INDEX_SCAN_UNIQUE,
+            false => self.raw.idxFlags &= !ffi::SQLITE_INDEX_SCAN_UNIQUE,
+        }
+    }
+}
+
+/// The module definition together with the auxiliary data
+///
+/// # Important
+/// SQLite does not copy the module definition, so it must live as long as the module is registered.
+struct Module<T>
+where
+    T: VirtualTable,
+{
+    /// The module definition
+    module: ffi::sqlite3_module,
+    /// The module-wide auxiliary data
+    aux: T::Aux,
+}
+
+/// A virtual table instance
+#[repr(C)]
+struct Table<T> {
+    /// The SQLite base struct
+    ///
+    /// # Important
+    /// This field must be the first field, so that a pointer to the base struct is also a pointer to the table
+    base: ffi::sqlite3_vtab,
+    /// The table implementation
+    table: T,
+}
+
+/// A virtual table cursor instance
+#[repr(C)]
+struct Cursor<C> {
+    /// The SQLite base struct
+    ///
+    /// # Important
+    /// This field must be the first field, so that a pointer to the base struct is also a pointer to the cursor
+    base: ffi::sqlite3_vtab_cursor,
+    /// The cursor implementation
+    cursor: C,
+}
+
+/// Registers a virtual table module
+///
+/// # Note
+/// If `eponymous_only` is `true`, the module can only be used as eponymous table (e.g. as table-valued function), but
+/// not via `CREATE VIRTUAL TABLE`.
+pub(in crate::api) fn create_module<T>(
+    sqlite: &Sqlite,
+    name: &str,
+    aux: T::Aux,
+    eponymous_only: bool,
+) -> Result<(), Error>
+where
+    T: VirtualTable + Send + 'static,
+{
+    let name = CString::new(name).map_err(|e| err!(with: e, "Invalid module name"))?;
+    let module = ffi::sqlite3_module {
+        iVersion: 1,
+        // Note: A module without `xCreate` is eponymous-only
+        xCreate: match eponymous_only {
+            true => None,
+            false => Some(create::<T>),
+        },
+        xConnect: Some(connect::<T>),
+        xBestIndex: Some(best_index::<T>),
+        xDisconnect: Some(disconnect::<T>),
+        xDestroy: Some(destroy::<T>),
+        xOpen: Some(open::<T>),
+        xClose: Some(close::<T>),
+        xFilter: Some(filter::<T>),
+        xNext: Some(next::<T>),
+        xEof: Some(eof::<T>),
+        xColumn: Some(column::<T>),
+        xRowid: Some(rowid::<T>),
+        // Note: A module without `xUpdate` is read-only
+        xUpdate: match T::WRITABLE {
+            true => Some(update::<T>),
+            false => None,
+        },
+        xBegin: None,
+        xSync: None,
+        xCommit: None,
+        xRollback: None,
+        xFindFunction: None,
+        xRename: None,
+        xSavepoint: None,
+        xRelease: None,
+        xRollbackTo: None,
+        xShadowName: None,
+        xIntegrity: None,
+    };
+
+    // Register the module
+    // Note: The client data is a boxed `Module<T>`, and SQLite calls the destructor even if the registration fails
+    let module = Box::into_raw(Box::new(Module::<T> { module, aux }));
+    let retval = unsafe {
+        ffi::sqlite3_create_module_v2(
+            sqlite.raw.as_ptr(),
+            name.as_ptr(),
+            &(*module).module,
+            module as *mut c_void,
+            Some(drop_boxed::<Module<T>>),
+        )
+    };
+    unsafe { ffiext::sqlite3_check_result(retval, sqlite.raw.as_ptr()) }
+}
+
+/// Creates a slice from a raw SQLite array
+///
+/// # Safety
+/// `data` must point to `len` valid elements if `len` is positive.
+unsafe fn raw_slice<'a, T>(data: *const T, len: c_int) -> &'a [T] {
+    match data.is_null() || len <= 0 {
+        true => &[],
+        false => unsafe { slice::from_raw_parts(data, len as usize) },
+    }
+}
+
+/// Creates a mutable slice from a raw SQLite array
+///
+/// # Safety
+/// `data` must point to `len` valid elements if `len` is positive.
+unsafe fn raw_slice_mut<'a, T>(data: *mut T, len: c_int) -> &'a mut [T] {
+    match data.is_null() || len <= 0 {
+        true => &mut [],
+        false => unsafe { slice::from_raw_parts_mut(data, len as usize) },
+    }
+}
+
+/// Calls a virtual table callback and converts a panic into an error
+fn catch<T, F>(callback: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error>,
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(callback));
+    result.unwrap_or_else(|_| Err(err!("Rust virtual table panicked")))
+}
+
+/// Sets the error message of the given table and returns the appropriate result code
+///
+/// # Safety
+/// `table` must be a valid table.
+unsafe fn table_error(table: *mut ffi::sqlite3_vtab, error: Error) -> c_int {
+    // Replace the error message
+    // Note: SQLite frees the error message with `sqlite3_free` once it has been reported
+    unsafe { ffi::sqlite3_free((*table).zErrMsg as *mut c_void) };
+    unsafe { (*table).zErrMsg = ffiext::sqlite3_malloc_str(&error.error) };
+    error.extended_code.unwrap_or(ffi::SQLITE_ERROR)
+}
+
+/// Reads a rowid argument
+fn rowid_arg(value: Option<&ValueRef>) -> Result<i64, Error> {
+    match value {
+        Some(ValueRef::Integer(rowid)) => Ok(*rowid),
+        _ => Err(err!("Invalid rowid")),
+    }
+}
+
+/// A function that creates or connects to a table
+type Constructor<T> = fn(&<T as VirtualTable>::Aux, &[&str]) -> Result<(String, T), Error>;
+
+/// Creates or connects to a table
+///
+/// # Safety
+/// This function must only be called from within a create or connect callback of `T`.
+unsafe fn construct<T>(
+    database: *mut ffi::sqlite3,
+    aux: *mut c_void,
+    argc: c_int,
+    argv: *const *const c_char,
+    table: *mut *mut ffi::sqlite3_vtab,
+    error: *mut *mut c_char,
+    constructor: Constructor<T>,
+) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The client data is a boxed `Module<T>` which lives until the destructor is called
+        let module = unsafe { &*(aux as *const Module<T>) };
+        let args = unsafe { raw_slice(argv, argc) };
+        let args: Vec<&str> = (args.iter())
+            .map(|arg| unsafe { CStr::from_ptr(*arg) }.to_str())
+            .collect::<Result<_, _>>()
+            .map_err(|e| err!(with: e, "Module argument is not valid UTF-8"))?;
+
+        // Create the table and declare the schema
+        let (schema, table) = constructor(&module.aux, &args)?;
+        let schema = CString::new(schema).map_err(|e| err!(with: e, "Invalid virtual table schema"))?;
+        let retval = unsafe { ffi::sqlite3_declare_vtab(database, schema.as_ptr()) };
+        unsafe { ffiext::sqlite3_check_result(retval, database) }?;
+
+        // Box the table
+        // Note: SQLite initializes the base struct
+        let base = ffi::sqlite3_vtab { pModule: ptr::null(), nRef: 0, zErrMsg: ptr::null_mut() };
+        Ok(Box::into_raw(Box::new(Table { base, table })))
+    });
+
+    // Set the table or the error
+    match result {
+        Ok(table_) => {
+            unsafe { *table = table_ as *mut ffi::sqlite3_vtab };
+            ffi::SQLITE_OK
+        }
+        Err(e) => {
+            unsafe { *error = ffiext::sqlite3_malloc_str(&e.error) };
+            e.extended_code.unwrap_or(ffi::SQLITE_ERROR)
+        }
+    }
+}
+
+/// Creates a table
+unsafe extern "C" fn create<T>(
+    database: *mut ffi::sqlite3,
+    aux: *mut c_void,
+    argc: c_int,
+    argv: *const *const c_char,
+    table: *mut *mut ffi::sqlite3_vtab,
+    error: *mut *mut c_char,
+) -> c_int
+where
+    T: VirtualTable,
+{
+    unsafe { construct(database, aux, argc, argv, table, error, T::create) }
+}
+
+/// Connects to a table
+unsafe extern "C" fn connect<T>(
+    database: *mut ffi::sqlite3,
+    aux: *mut c_void,
+    argc: c_int,
+    argv: *const *const c_char,
+    table: *mut *mut ffi::sqlite3_vtab,
+    error: *mut *mut c_char,
+) -> c_int
+where
+    T: VirtualTable,
+{
+    unsafe { construct(database, aux, argc, argv, table, error, T::connect) }
+}
+
+/// Disconnects from a table
+unsafe extern "C" fn disconnect<T>(table: *mut ffi::sqlite3_vtab) -> c_int {
+    // Note: We must not unwind into SQLite, so we catch and discard any panic during drop
+    let table = unsafe { Box::from_raw(table as *mut Table<T>) };
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(table)));
+    ffi::SQLITE_OK
+}
+
+/// Destroys a table and disconnects from it
+unsafe extern "C" fn destroy<T>(table: *mut ffi::sqlite3_vtab) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The table is a boxed `Table<T>` which lives until it is disconnected
+        let table = unsafe { &mut (*(table as *mut Table<T>)).table };
+        table.destroy()
+    });
+
+    // Disconnect from the table or keep it if it could not be destroyed
+    match result {
+        Ok(()) => unsafe { disconnect::<T>(table) },
+        Err(e) => unsafe { table_error(table, e) },
+    }
+}
+
+/// Selects the best query plan
+unsafe extern "C" fn best_index<T>(table: *mut ffi::sqlite3_vtab, info: *mut ffi::sqlite3_index_info) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The table is a boxed `Table<T>` which lives until it is disconnected
+        let table = unsafe { &(*(table as *const Table<T>)).table };
+        let mut info = IndexInfo { raw: unsafe { &mut *info } };
+        table.best_index(&mut info)
+    });
+
+    // Set the error if any
+    match result {
+        Ok(()) => ffi::SQLITE_OK,
+        Err(e) => unsafe { table_error(table, e) },
+    }
+}
+
+/// Opens a cursor
+unsafe extern "C" fn open<T>(table: *mut ffi::sqlite3_vtab, cursor: *mut *mut ffi::sqlite3_vtab_cursor) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The table is a boxed `Table<T>` which lives until it is disconnected
+        let table = unsafe { &(*(table as *const Table<T>)).table };
+        let cursor = table.open()?;
+
+        // Box the cursor
+        // Note: SQLite initializes the base struct
+        let base = ffi::sqlite3_vtab_cursor { pVtab: ptr::null_mut() };
+        Ok(Box::into_raw(Box::new(Cursor { base, cursor })))
+    });
+
+    // Set the cursor or the error
+    match result {
+        Ok(cursor_) => {
+            unsafe { *cursor = cursor_ as *mut ffi::sqlite3_vtab_cursor };
+            ffi::SQLITE_OK
+        }
+        Err(e) => unsafe { table_error(table, e) },
+    }
+}
+
+/// Closes a cursor
+unsafe extern "C" fn close<T>(cursor: *mut ffi::sqlite3_vtab_cursor) -> c_int
+where
+    T: VirtualTable,
+{
+    // Note: We must not unwind into SQLite, so we catch and discard any panic during drop
+    let cursor = unsafe { Box::from_raw(cursor as *mut Cursor<T::Cursor>) };
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(cursor)));
+    ffi::SQLITE_OK
+}
+
+/// Starts a new search
+unsafe extern "C" fn filter<T>(
+    cursor: *mut ffi::sqlite3_vtab_cursor,
+    idx_num: c_int,
+    idx_str: *const c_char,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The cursor is a boxed `Cursor<T::Cursor>` which lives until it is closed
+        let cursor = unsafe { &mut (*(cursor as *mut Cursor<T::Cursor>)).cursor };
+        let idx_str = match idx_str.is_null() {
+            true => None,
+            false => {
+                Some(unsafe { CStr::from_ptr(idx_str) }.to_str().map_err(|e| err!(with: e, "Invalid plan string"))?)
+            }
+        };
+        let args = unsafe { ffiext::sqlite3_value_refs(argc, argv) }?;
+        cursor.filter(idx_num, idx_str, &args)
+    });
+
+    // Set the error if any
+    match result {
+        Ok(()) => ffi::SQLITE_OK,
+        Err(e) => unsafe { table_error((*cursor).pVtab, e) },
+    }
+}
+
+/// Advances a cursor
+unsafe extern "C" fn next<T>(cursor: *mut ffi::sqlite3_vtab_cursor) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The cursor is a boxed `Cursor<T::Cursor>` which lives until it is closed
+        let cursor = unsafe { &mut (*(cursor as *mut Cursor<T::Cursor>)).cursor };
+        cursor.next()
+    });
+
+    // Set the error if any
+    match result {
+        Ok(()) => ffi::SQLITE_OK,
+        Err(e) => unsafe { table_error((*cursor).pVtab, e) },
+    }
+}
+
+/// Checks if a cursor has been advanced past the last row
+unsafe extern "C" fn eof<T>(cursor: *mut ffi::sqlite3_vtab_cursor) -> c_int
+where
+    T: VirtualTable,
+{
+    let eof = panic::catch_unwind(AssertUnwindSafe(|| {
+        // Note: The cursor is a boxed `Cursor<T::Cursor>` which lives until it is closed
+        let cursor = unsafe { &(*(cursor as *const Cursor<T::Cursor>)).cursor };
+        cursor.eof()
+    }));
+
+    // Note: This callback cannot fail, so we stop the iteration on panic
+    c_int::from(eof.unwrap_or(true))
+}
+
+/// Reads a column of the current row
+unsafe extern "C" fn column<T>(
+    cursor: *mut ffi::sqlite3_vtab_cursor,
+    context: *mut ffi::sqlite3_context,
+    column: c_int,
+) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The cursor is a boxed `Cursor<T::Cursor>` which lives until it is closed
+        let cursor = unsafe { &(*(cursor as *const Cursor<T::Cursor>)).cursor };
+        let column = usize::try_from(column).map_err(|e| err!(with: e, "Invalid column index"))?;
+        cursor.column(column)
+    });
+
+    // Set the result
+    let retval = match &result {
+        Ok(_) => ffi::SQLITE_OK,
+        Err(e) => e.extended_code.unwrap_or(ffi::SQLITE_ERROR),
+    };
+    unsafe { ffiext::sqlite3_result(context, result) };
+    retval
+}
+
+/// Gets the rowid of the current row
+unsafe extern "C" fn rowid<T>(cursor: *mut ffi::sqlite3_vtab_cursor, rowid: *mut ffi::sqlite3_int64) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The cursor is a boxed `Cursor<T::Cursor>` which lives until it is closed
+        let cursor = unsafe { &(*(cursor as *const Cursor<T::Cursor>)).cursor };
+        cursor.rowid()
+    });
+
+    // Set the rowid or the error
+    match result {
+        Ok(rowid_) => {
+            unsafe { *rowid = rowid_ };
+            ffi::SQLITE_OK
+        }
+        Err(e) => unsafe { table_error((*cursor).pVtab, e) },
+    }
+}
+
+/// Applies a change to a table
+unsafe extern "C" fn update<T>(
+    table: *mut ffi::sqlite3_vtab,
+    argc: c_int,
+    argv: *mut *mut ffi::sqlite3_value,
+    rowid: *mut ffi::sqlite3_int64,
+) -> c_int
+where
+    T: VirtualTable,
+{
+    let result = catch(|| {
+        // Note: The table is a boxed `Table<T>` which lives until it is disconnected
+        let table = unsafe { &mut (*(table as *mut Table<T>)).table };
+        let args = unsafe { ffiext::sqlite3_value_refs(argc, argv) }?;
+
+        // Decode the change (see <https://www.sqlite.org/vtab.html#xupdate>)
+        let values = args.get(2..).unwrap_or_default();
+        let change = match (args.len(), args.first(), args.get(1)) {
+            (1, first, _) => VTabChange::Delete { rowid: rowid_arg(first)? },
+            (_, Some(ValueRef::Null), Some(ValueRef::Null)) => VTabChange::Insert { rowid: None, values },
+            (_, Some(ValueRef::Null), second) => VTabChange::Insert { rowid: Some(rowid_arg(second)?), values },
+            (_, first, second) => {
+                VTabChange::Update { old_rowid: rowid_arg(first)?, new_rowid: rowid_arg(second)?, values }
+            }
+        };
+
+        // Apply the change
+        let is_insert = matches!(change, VTabChange::Insert { .. });
+        let rowid = table.update(change)?;
+        Ok(is_insert.then_some(rowid))
+    });
+
+    // Set the rowid or the error
+    match result {
+        Ok(Some(rowid_)) => {
+            unsafe { *rowid = rowid_ };
+            ffi::SQLITE_OK
+        }
+        Ok(None) => ffi::SQLITE_OK,
+        Err(e) => unsafe { table_error(table, e) },
+    }
+}
@@ -1,7 +1,9 @@
 //! Implements the crate's error type
 #![cfg(feature = "api")]
 
+use crate::ffi;
 use std::backtrace::{Backtrace, BacktraceStatus};
+use std::ffi::c_int;
 use std::fmt::{self, Display, Formatter};
 
 /// Creates a new error
@@ -18,11 +20,111 @@ macro_rules! err {
     }};
 }
 
+/// The kind of an error
+///
+/// # Note
+/// Except for [`ErrorKind::Api`], the kinds correspond to the primary SQLite result codes (see
+/// <https://www.sqlite.org/rescode.html>).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The error did not originate from SQLite but from the Rust API (e.g. an invalid argument or a failed conversion)
+    Api,
+    /// Generic error (`SQLITE_ERROR`)
+    Error,
+    /// Internal malfunction (`SQLITE_INTERNAL`)
+    Internal,
+    /// Access permission denied (`SQLITE_PERM`)
+    Permission,
+    /// Operation was aborted (`SQLITE_ABORT`)
+    Abort,
+    /// The database file is locked (`SQLITE_BUSY`)
+    Busy,
+    /// A table in the database is locked (`SQLITE_LOCKED`)
+    Locked,
+    /// A memory allocation failed (`SQLITE_NOMEM`)
+    NoMemory,
+    /// Attempt to write to a readonly database (`SQLITE_READONLY`)
+    ReadOnly,
+    /// Operation was interrupted (`SQLITE_INTERRUPT`)
+    Interrupt,
+    /// Some kind of disk I/O error occurred (`SQLITE_IOERR`)
+    Io,
+    /// The database disk image is malformed (`SQLITE_CORRUPT`)
+    Corrupt,
+    /// Unknown opcode or file control (`SQLITE_NOTFOUND`)
+    NotFound,
+    /// Insertion failed because the database is full (`SQLITE_FULL`)
+    Full,
+    /// Unable to open the database file (`SQLITE_CANTOPEN`)
+    CantOpen,
+    /// Database lock protocol error (`SQLITE_PROTOCOL`)
+    Protocol,
+    /// The database schema changed (`SQLITE_SCHEMA`)
+    Schema,
+    /// String or BLOB exceeds size limit (`SQLITE_TOOBIG`)
+    TooBig,
+    /// Abort due to constraint violation (`SQLITE_CONSTRAINT`)
+    Constraint,
+    /// Data type mismatch (`SQLITE_MISMATCH`)
+    Mismatch,
+    /// Library used incorrectly (`SQLITE_MISUSE`)
+    Misuse,
+    /// Uses OS features not supported on host (`SQLITE_NOLFS`)
+    NoLfs,
+    /// Authorization denied (`SQLITE_AUTH`)
+    Auth,
+    /// Bind parameter or column index out of range (`SQLITE_RANGE`)
+    Range,
+    /// File opened that is not a database file (`SQLITE_NOTADB`)
+    NotADatabase,
+    /// Any other or unknown result code
+    Unknown,
+}
+impl ErrorKind {
+    /// Gets the error kind for the given primary or extended SQLite result code
+    pub const fn from_code(code: c_int) -> Self {
+        // Note: The primary result code is always the least significant byte of an extended result code
+        match code & 0xff {
+            ffi::SQLITE_ERROR => Self::Error,
+            ffi::SQLITE_INTERNAL => Self::Internal,
+            ffi::SQLITE_PERM => Self::Permission,
+            ffi::SQLITE_ABORT => Self::Abort,
+            ffi::SQLITE_BUSY => Self::Busy,
+            ffi::SQLITE_LOCKED => Self::Locked,
+            ffi::SQLITE_NOMEM => Self::NoMemory,
+            ffi::SQLITE_READONLY => Self::ReadOnly,
+            ffi::SQLITE_INTERRUPT => Self::Interrupt,
+            ffi::SQLITE_IOERR => Self::Io,
+            ffi::SQLITE_CORRUPT => Self::Corrupt,
+            ffi::SQLITE_NOTFOUND => Self::NotFound,
+            ffi::SQLITE_FULL => Self::Full,
+            ffi::SQLITE_CANTOPEN => Self::CantOpen,
+            ffi::SQLITE_PROTOCOL => Self::Protocol,
+            ffi::SQLITE_SCHEMA => Self::Schema,
+            ffi::SQLITE_TOOBIG => Self::TooBig,
+            ffi::SQLITE_CONSTRAINT => Self::Constraint,
+            ffi::SQLITE_MISMATCH => Self::Mismatch,
+            ffi::SQLITE_MISUSE => Self::Misuse,
+            ffi::SQLITE_NOLFS => Self::NoLfs,
+            ffi::SQLITE_AUTH => Self::Auth,
+            ffi::SQLITE_RANGE => Self::Range,
+            ffi::SQLITE_NOTADB => Self::NotADatabase,
+            _ => Self::Unknown,
+        }
+    }
+}
+
 /// The crates error type
 #[derive(Debug)]
 pub struct Error {
     /// The error description
     pub error: String,
+    /// The error kind
+    pub kind: ErrorKind,
+    /// The extended SQLite result code if the error originates from SQLite
+    pub extended_code: Option<c_int>,
+    /// The byte offset into the SQL text the error refers to, if any (e.g. for syntax errors)
+    pub offset: Option<usize>,
     /// The underlying error
     pub source: Option<Box<dyn std::error::Error + Send>>,
     /// The backtrace
@@ -32,7 +134,19 @@ impl Error {
     /// Creates a new error and captures a backtrace
     pub fn new(error: String, source: Option<Box<dyn std::error::Error + Send>>) -> Self {
         let backtrace = Backtrace::capture();
-        Self { error, source, backtrace }
+        Self { error, kind: ErrorKind::Api, extended_code: None, offset: None, source, backtrace }
+    }
+    /// Creates a new error from an extended SQLite result code and captures a backtrace
+    pub fn sqlite(error: String, extended_code: c_int, offset: Option<usize>) -> Self {
+        let kind = ErrorKind::from_code(extended_code);
+        let backtrace = Backtrace::capture();
+        Self { error, kind, extended_code: Some(extended_code), offset, source: None, backtrace }
+    }
+
+    /// The primary SQLite result code if the error originates from SQLite
+    pub fn code(&self) -> Option<c_int> {
+        // Note: The primary result code is always the least significant byte of an extended result code
+        self.extended_code.map(|code| code & 0xff)
     }
 
     /// Whether the error has captured a backtrace or not
@@ -14,6 +14,199 @@ unsafe extern "C" {
     pub unsafe fn sqlite3_transient() -> sqlite3_destructor_type;
 }
 
+// Pre-update hook bindings
+// Note: These functions are only declared if `SQLITE_ENABLE_PREUPDATE_HOOK` is defined, so they are not part of the
+//  generated bindings
+#[cfg(feature = "preupdate-hook")]
+unsafe extern "C" {
+    //void *sqlite3_preupdate_hook(sqlite3 *db, void(*xPreUpdate)(...), void*)
+    pub fn sqlite3_preupdate_hook(
+        db: *mut sqlite3,
+        xPreUpdate: ::core::option::Option<
+            unsafe extern "C" fn(
+                pCtx: *mut ::core::ffi::c_void,
+                db: *mut sqlite3,
+                op: ::core::ffi::c_int,
+                zDb: *const ::core::ffi::c_char,
+                zName: *const ::core::ffi::c_char,
+                iKey1: sqlite3_int64,
+                iKey2: sqlite3_int64,
+            ),
+        >,
+        arg1: *mut ::core::ffi::c_void,
+    ) -> *mut ::core::ffi::c_void;
+    //int sqlite3_preupdate_old(sqlite3 *, int, sqlite3_value **)
+    pub fn sqlite3_preupdate_old(
+        arg1: *mut sqlite3,
+        arg2: ::core::ffi::c_int,
+        arg3: *mut *mut sqlite3_value,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3_preupdate_count(sqlite3 *)
+    pub fn sqlite3_preupdate_count(arg1: *mut sqlite3) -> ::core::ffi::c_int;
+    //int sqlite3_preupdate_depth(sqlite3 *)
+    pub fn sqlite3_preupdate_depth(arg1: *mut sqlite3) -> ::core::ffi::c_int;
+    //int sqlite3_preupdate_new(sqlite3 *, int, sqlite3_value **)
+    pub fn sqlite3_preupdate_new(
+        arg1: *mut sqlite3,
+        arg2: ::core::ffi::c_int,
+        arg3: *mut *mut sqlite3_value,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3_preupdate_blobwrite(sqlite3 *)
+    pub fn sqlite3_preupdate_blobwrite(arg1: *mut sqlite3) -> ::core::ffi::c_int;
+}
+
+// Session extension bindings
+// Note: These functions are only declared if `SQLITE_ENABLE_SESSION` is defined, so they are not part of the generated
+//  bindings
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_DATA: i32 = 1;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_NOTFOUND: i32 = 2;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_CONFLICT: i32 = 3;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_CONSTRAINT: i32 = 4;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_FOREIGN_KEY: i32 = 5;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_OMIT: i32 = 0;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_REPLACE: i32 = 1;
+#[cfg(feature = "session")]
+pub const SQLITE_CHANGESET_ABORT: i32 = 2;
+#[cfg(feature = "session")]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sqlite3_session {
+    _unused: [u8; 0],
+}
+#[cfg(feature = "session")]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct sqlite3_changeset_iter {
+    _unused: [u8; 0],
+}
+#[cfg(feature = "session")]
+unsafe extern "C" {
+    //int sqlite3session_create(sqlite3 *db, const char *zDb, sqlite3_session **ppSession)
+    pub fn sqlite3session_create(
+        db: *mut sqlite3,
+        zDb: *const ::core::ffi::c_char,
+        ppSession: *mut *mut sqlite3_session,
+    ) -> ::core::ffi::c_int;
+    //void sqlite3session_delete(sqlite3_session *pSession)
+    pub fn sqlite3session_delete(pSession: *mut sqlite3_session);
+    //int sqlite3session_enable(sqlite3_session *pSession, int bEnable)
+    pub fn sqlite3session_enable(pSession: *mut sqlite3_session, bEnable: ::core::ffi::c_int) -> ::core::ffi::c_int;
+    //int sqlite3session_attach(sqlite3_session *pSession, const char *zTab)
+    pub fn sqlite3session_attach(
+        pSession: *mut sqlite3_session,
+        zTab: *const ::core::ffi::c_char,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3session_changeset(sqlite3_session *pSession, int *pnChangeset, void **ppChangeset)
+    pub fn sqlite3session_changeset(
+        pSession: *mut sqlite3_session,
+        pnChangeset: *mut ::core::ffi::c_int,
+        ppChangeset: *mut *mut ::core::ffi::c_void,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3session_patchset(sqlite3_session *pSession, int *pnPatchset, void **ppPatchset)
+    pub fn sqlite3session_patchset(
+        pSession: *mut sqlite3_session,
+        pnPatchset: *mut ::core::ffi::c_int,
+        ppPatchset: *mut *mut ::core::ffi::c_void,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3session_isempty(sqlite3_session *pSession)
+    pub fn sqlite3session_isempty(pSession: *mut sqlite3_session) -> ::core::ffi::c_int;
+    //int sqlite3changeset_start(sqlite3_changeset_iter **pp, int nChangeset, void *pChangeset)
+    pub fn sqlite3changeset_start(
+        pp: *mut *mut sqlite3_changeset_iter,
+        nChangeset: ::core::ffi::c_int,
+        pChangeset: *mut ::core::ffi::c_void,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_next(sqlite3_changeset_iter *pIter)
+    pub fn sqlite3changeset_next(pIter: *mut sqlite3_changeset_iter) -> ::core::ffi::c_int;
+    //int sqlite3changeset_op(sqlite3_changeset_iter *pIter, const char **pzTab, int *pnCol, int *pOp, int *pbIndirect)
+    pub fn sqlite3changeset_op(
+        pIter: *mut sqlite3_changeset_iter,
+        pzTab: *mut *const ::core::ffi::c_char,
+        pnCol: *mut ::core::ffi::c_int,
+        pOp: *mut ::core::ffi::c_int,
+        pbIndirect: *mut ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_pk(sqlite3_changeset_iter *pIter, unsigned char **pabPK, int *pnCol)
+    pub fn sqlite3changeset_pk(
+        pIter: *mut sqlite3_changeset_iter,
+        pabPK: *mut *mut ::core::ffi::c_uchar,
+        pnCol: *mut ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_old(sqlite3_changeset_iter *pIter, int iVal, sqlite3_value **ppValue)
+    pub fn sqlite3changeset_old(
+        pIter: *mut sqlite3_changeset_iter,
+        iVal: ::core::ffi::c_int,
+        ppValue: *mut *mut sqlite3_value,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_new(sqlite3_changeset_iter *pIter, int iVal, sqlite3_value **ppValue)
+    pub fn sqlite3changeset_new(
+        pIter: *mut sqlite3_changeset_iter,
+        iVal: ::core::ffi::c_int,
+        ppValue: *mut *mut sqlite3_value,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_conflict(sqlite3_changeset_iter *pIter, int iVal, sqlite3_value **ppValue)
+    pub fn sqlite3changeset_conflict(
+        pIter: *mut sqlite3_changeset_iter,
+        iVal: ::core::ffi::c_int,
+        ppValue: *mut *mut sqlite3_value,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_fk_conflicts(sqlite3_changeset_iter *pIter, int *pnOut)
+    pub fn sqlite3changeset_fk_conflicts(
+        pIter: *mut sqlite3_changeset_iter,
+        pnOut: *mut ::core::ffi::c_int,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_finalize(sqlite3_changeset_iter *pIter)
+    pub fn sqlite3changeset_finalize(pIter: *mut sqlite3_changeset_iter) -> ::core::ffi::c_int;
+    //int sqlite3changeset_invert(int nIn, const void *pIn, int *pnOut, void **ppOut)
+    pub fn sqlite3changeset_invert(
+        nIn: ::core::ffi::c_int,
+        pIn: *const ::core::ffi::c_void,
+        pnOut: *mut ::core::ffi::c_int,
+        ppOut: *mut *mut ::core::ffi::c_void,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_concat(int nA, void *pA, int nB, void *pB, int *pnOut, void **ppOut)
+    pub fn sqlite3changeset_concat(
+        nA: ::core::ffi::c_int,
+        pA: *mut ::core::ffi::c_void,
+        nB: ::core::ffi::c_int,
+        pB: *mut ::core::ffi::c_void,
+        pnOut: *mut ::core::ffi::c_int,
+        ppOut: *mut *mut ::core::ffi::c_void,
+    ) -> ::core::ffi::c_int;
+    //int sqlite3changeset_apply(sqlite3 *db, int nChangeset, void *pChangeset, int(*xFilter)(...),
+    //  int(*xConflict)(...), void *pCtx)
+    pub fn sqlite3changeset_apply(
+        db: *mut sqlite3,
+        nChangeset: ::core::ffi::c_int,
+        pChangeset: *mut ::core::ffi::c_void,
+        xFilter: ::core::option::Option<
+            unsafe extern "C" fn(
+                pCtx: *mut ::core::ffi::c_void,
+                zTab: *const ::core::ffi::c_char,
+            ) -> ::core::ffi::c_int,
+        >,
+        xConflict: ::core::option::Option<
+            unsafe extern "C" fn(
+                pCtx: *mut ::core::ffi::c_void,
+                eConflict: ::core::ffi::c_int,
+                p: *mut sqlite3_changeset_iter,
+            ) -> ::core::ffi::c_int,
+        >,
+        pCtx: *mut ::core::ffi::c_void,
+    ) -> ::core::ffi::c_int;
+}
+
+/// The destructor constant to define "static" ownership (i.e. tell SQLite that the value remains valid and unchanged
+/// as long as it is needed; see `SQLITE_STATIC`)
+pub const SQLITE_STATIC: sqlite3_destructor_type = None;
+
 /// Asserts that sqlite is compiled threadsafe
 #[test]
 fn assert_threadsafe() {
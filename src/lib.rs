@@ -19,6 +19,8 @@ pub mod ffi;
 
 #[cfg(feature = "api")]
 pub use api::sqlite::Sqlite;
+#[cfg(feature = "derive")]
+pub use sqlite_tiny_derive::{FromRow, ToParams};
 
 /// Returns the semver tuple for the distributed sqlite version as `(major, minor, patch)`-tuple
 pub fn version() -> (i32, i32, i32) {
@@ -1,6 +1,22 @@
 #![cfg(feature = "api")]
 
-use sqlite_tiny::Sqlite;
+use sqlite_tiny::api::backup::{Progress, RetryPolicy};
+use sqlite_tiny::api::collation::Collation;
+use sqlite_tiny::api::function::{Aggregate, WindowFunction};
+use sqlite_tiny::api::hooks::UpdateOp;
+use sqlite_tiny::api::row::{FromRow, Row};
+use sqlite_tiny::api::trace::{TraceEvent, TraceMask};
+use sqlite_tiny::api::transaction::TransactionBehavior;
+use sqlite_tiny::api::types::{SqliteType, ValueRef};
+use sqlite_tiny::api::vtab::{ConstraintOp, IndexInfo, VTabChange, VTabCursor, VirtualTable};
+use sqlite_tiny::error::{Error, ErrorKind};
+use sqlite_tiny::{ffi, Sqlite};
+use std::cell::Cell;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
 
 /// A schema for a basic test table
 const CREATE_TABLE: &str = "
@@ -106,3 +122,1399 @@ fn success() {
         .expect("missing expected result");
     assert_eq!(row.read::<i32>(0).expect("failed to read row count"), 0);
 }
+
+#[test]
+fn error_codes() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE unique_test (value INTEGER UNIQUE NOT NULL)").expect("failed to create table");
+
+    // Provoke a UNIQUE violation
+    database.execute("INSERT INTO unique_test (value) VALUES (7)").expect("failed to insert value");
+    let error = database.execute("INSERT INTO unique_test (value) VALUES (7)").expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Constraint);
+    assert_eq!(error.code(), Some(ffi::SQLITE_CONSTRAINT));
+    assert_eq!(error.extended_code, Some(ffi::SQLITE_CONSTRAINT_UNIQUE));
+
+    // Provoke a syntax error
+    let error = database.query("SELECT * FROM unique_test WHERE valeu = 7").expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Error);
+    assert_eq!(error.offset, Some(32));
+
+    // Provoke an out-of-range binding
+    let query = database.query("SELECT * FROM unique_test WHERE value = ?").expect("failed to prepare query");
+    let error = query.bind(2, 7).expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Range);
+
+    // Provoke a conversion error
+    let query = database.query("SELECT * FROM unique_test WHERE value = ?").expect("failed to prepare query");
+    let error = query.bind(1, u64::MAX).expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Api);
+    assert_eq!(error.code(), None);
+}
+
+#[test]
+fn transaction() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE transaction_test (value INTEGER NOT NULL)").expect("failed to create table");
+
+    // Counts the rows in the test table
+    let count = || {
+        (database.query("SELECT COUNT(*) FROM transaction_test"))
+            .and_then(|query| query.execute())
+            .and_then(|result| result.row())
+            .and_then(|row| row.read::<i64>(0))
+            .expect("failed to count rows")
+    };
+
+    // Commit a transaction
+    let transaction = database.transaction(TransactionBehavior::Immediate).expect("failed to begin transaction");
+    transaction.execute("INSERT INTO transaction_test (value) VALUES (1)").expect("failed to insert value");
+    transaction.commit().expect("failed to commit transaction");
+    assert_eq!(count(), 1);
+
+    // Roll back a transaction explicitly
+    let transaction = database.transaction(TransactionBehavior::Deferred).expect("failed to begin transaction");
+    transaction.execute("INSERT INTO transaction_test (value) VALUES (2)").expect("failed to insert value");
+    transaction.rollback().expect("failed to roll back transaction");
+    assert_eq!(count(), 1);
+
+    // Roll back a transaction on drop and refuse to nest transactions
+    {
+        let transaction = database.transaction(TransactionBehavior::Exclusive).expect("failed to begin transaction");
+        transaction.execute("INSERT INTO transaction_test (value) VALUES (3)").expect("failed to insert value");
+        database.transaction(TransactionBehavior::Deferred).expect_err("unexpected nested transaction");
+        assert!(database.in_transaction());
+    }
+    assert!(!database.in_transaction());
+    assert_eq!(count(), 1);
+}
+
+#[test]
+fn savepoint() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE savepoint_test (value INTEGER NOT NULL)").expect("failed to create table");
+
+    // Counts the rows in the test table
+    let count = || {
+        (database.query("SELECT COUNT(*) FROM savepoint_test"))
+            .and_then(|query| query.execute())
+            .and_then(|result| result.row())
+            .and_then(|row| row.read::<i64>(0))
+            .expect("failed to count rows")
+    };
+
+    // Nest savepoints within a transaction
+    let mut transaction = database.transaction(TransactionBehavior::Deferred).expect("failed to begin transaction");
+    {
+        let mut outer = transaction.savepoint().expect("failed to create savepoint");
+        outer.execute("INSERT INTO savepoint_test (value) VALUES (1)").expect("failed to insert value");
+        {
+            // Roll back the innermost savepoint on drop
+            let mut inner = outer.savepoint().expect("failed to create savepoint");
+            inner.execute("INSERT INTO savepoint_test (value) VALUES (2)").expect("failed to insert value");
+            let innermost = inner.savepoint().expect("failed to create savepoint");
+            innermost.execute("INSERT INTO savepoint_test (value) VALUES (3)").expect("failed to insert value");
+        }
+        assert_eq!(count(), 1);
+
+        // Roll back a savepoint explicitly
+        let inner = outer.savepoint().expect("failed to create savepoint");
+        inner.execute("INSERT INTO savepoint_test (value) VALUES (4)").expect("failed to insert value");
+        inner.rollback().expect("failed to roll back savepoint");
+        outer.release().expect("failed to release savepoint");
+    }
+    transaction.commit().expect("failed to commit transaction");
+    assert_eq!(count(), 1);
+
+    // Use a savepoint without a transaction
+    let savepoint = database.savepoint().expect("failed to create savepoint");
+    savepoint.execute("INSERT INTO savepoint_test (value) VALUES (5)").expect("failed to insert value");
+    savepoint.release().expect("failed to release savepoint");
+    assert!(!database.in_transaction());
+    assert_eq!(count(), 2);
+}
+
+#[test]
+fn named_parameters() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE named_test (a INTEGER, b TEXT, c REAL)").expect("failed to create table");
+
+    // Inspect parameters
+    const INSERT_QUERY: &str = "INSERT INTO named_test (a, b, c) VALUES (:a, @b, $c)";
+    let query = database.query(INSERT_QUERY).expect("failed to prepare query");
+    assert_eq!(query.parameter_count(), 3);
+    assert_eq!(query.parameter_name(1), Some(":a"));
+    assert_eq!(query.parameter_name(2), Some("@b"));
+    assert_eq!(query.parameter_name(3), Some("$c"));
+    assert_eq!(query.parameter_name(4), None);
+
+    // Bind parameters by name
+    (query.bind_named(":a", 7))
+        .and_then(|query| query.bind_named("$c", 4.4))
+        .and_then(|query| query.bind_named("@b", "Testolope"))
+        .and_then(|query| query.execute())
+        .expect("failed to execute query");
+
+    // Read row back
+    let row = (database.query("SELECT a, b, c FROM named_test"))
+        .and_then(|query| query.execute())
+        .and_then(|result| result.row())
+        .expect("failed to read from test database");
+    assert_eq!(row.read::<i64>(0).expect("failed to read integer"), 7);
+    assert_eq!(row.read::<String>(1).expect("failed to read string"), "Testolope");
+    assert_eq!(row.read::<f64>(2).expect("failed to read real"), 4.4);
+
+    // Bind unknown parameter
+    let query = database.query(INSERT_QUERY).expect("failed to prepare query");
+    let error = query.bind_named(":d", 7).expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Api);
+}
+
+#[test]
+fn column_names() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE column_test (id INTEGER NOT NULL, Name TEXT)").expect("failed to create table");
+    database.execute("INSERT INTO column_test (id, Name) VALUES (7, 'Testolope')").expect("failed to insert row");
+
+    // Inspect columns before the first row is fetched
+    const SELECT_QUERY: &str = "SELECT id, Name, id * 2 AS double FROM column_test";
+    let answer = database.query(SELECT_QUERY).and_then(|query| query.execute()).expect("failed to execute query");
+    assert_eq!(answer.column_count(), 3);
+    assert_eq!(answer.column_names().expect("failed to get column names"), ["id", "Name", "double"]);
+    assert_eq!(answer.column_decltype(0).expect("failed to get decltype"), Some("INTEGER".to_string()));
+    assert_eq!(answer.column_decltype(2).expect("failed to get decltype"), None);
+    answer.column_name(3).expect_err("unexpected column name");
+
+    // Inspect column origins
+    #[cfg(feature = "column-metadata")]
+    {
+        assert_eq!(answer.column_database_name(1).expect("failed to get database"), Some("main".to_string()));
+        assert_eq!(answer.column_table_name(1).expect("failed to get table"), Some("column_test".to_string()));
+        assert_eq!(answer.column_origin_name(2).expect("failed to get origin"), None);
+    }
+
+    // Read values by name
+    let row = answer.row().expect("failed to get row");
+    assert_eq!(row.read_by_name::<i64>("id").expect("failed to read id"), 7);
+    assert_eq!(row.read_by_name::<String>("name").expect("failed to read name"), "Testolope");
+    assert_eq!(row.read_by_name::<i64>("double").expect("failed to read expression"), 14);
+    row.read_by_name::<i64>("unknown").expect_err("unexpected column");
+}
+
+#[test]
+fn reuse_statement() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE reuse_test (a INTEGER, b INTEGER)").expect("failed to create table");
+
+    // Insert many rows with a single statement and keep the bindings
+    let mut query = database.query("INSERT INTO reuse_test (a, b) VALUES (?, ?)").expect("failed to prepare query");
+    query = query.bind(2, 7).expect("failed to bind value");
+    for value in 0..1000 {
+        let answer = (query.bind(1, value)).and_then(|query| query.execute()).expect("failed to execute query");
+        query = answer.reset(false);
+    }
+
+    // Insert a row with cleared bindings
+    let query = query.bind(1, 1000).expect("failed to bind value");
+    query.clear_bindings().execute().expect("failed to execute query");
+
+    // Re-run a select statement with fresh parameters
+    let mut query = database.query("SELECT COUNT(*) FROM reuse_test WHERE b IS ?").expect("failed to prepare query");
+    for (b, expected) in [(Some(7), 1000), (None, 1)] {
+        let mut answer = (query.bind(1, b)).and_then(|query| query.execute()).expect("failed to execute query");
+        let row = answer.next_row().expect("failed to read row").expect("missing expected row");
+        assert_eq!(row.read::<i64>(0).expect("failed to read row count"), expected);
+        query = answer.reset(true);
+    }
+}
+
+#[test]
+fn statement_cache() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE cache_test (a INTEGER, b INTEGER)").expect("failed to create table");
+
+    // Insert rows with cached statements
+    const INSERT_QUERY: &str = "INSERT INTO cache_test (a, b) VALUES (?, ?)";
+    for value in 0..100 {
+        // Note: Bindings must have been cleared, so `b` is NULL for odd values
+        let query = database.query_cached(INSERT_QUERY).expect("failed to prepare query");
+        let query = query.bind(1, value).expect("failed to bind value");
+        let query = match value % 2 {
+            0 => query.bind(2, value).expect("failed to bind value"),
+            _ => query,
+        };
+        query.execute().expect("failed to execute query");
+    }
+
+    // Use the same cached statement concurrently
+    const COUNT_QUERY: &str = "SELECT COUNT(*) FROM cache_test WHERE b IS NULL";
+    let count_a =
+        database.query_cached(COUNT_QUERY).and_then(|query| query.execute()).expect("failed to execute query");
+    let count_b =
+        database.query_cached(COUNT_QUERY).and_then(|query| query.execute()).expect("failed to execute query");
+    for answer in [count_a, count_b] {
+        let row = answer.row().expect("failed to get row");
+        assert_eq!(row.read::<i64>(0).expect("failed to read row count"), 50);
+    }
+
+    // Flush the cache after a schema change and disable it
+    database.execute("ALTER TABLE cache_test ADD COLUMN c INTEGER DEFAULT 7").expect("failed to alter table");
+    database.flush_statement_cache();
+    database.set_statement_cache_capacity(0);
+    let row = (database.query_cached("SELECT SUM(c) FROM cache_test"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .expect("failed to read from test database");
+    assert_eq!(row.read::<i64>(0).expect("failed to read sum"), 700);
+
+    // Refuse empty statements
+    database.query_cached("-- nothing").expect_err("unexpected success");
+}
+
+/// A test struct to map rows into
+#[derive(Debug, PartialEq)]
+struct Item {
+    id: i64,
+    name: String,
+}
+impl FromRow for Item {
+    fn from_row(row: &Row) -> Result<Self, Error> {
+        Ok(Self { id: row.read_by_name("id")?, name: row.read_by_name("name")? })
+    }
+}
+
+#[test]
+fn map_rows() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database
+        .execute("CREATE TABLE map_test (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+        .expect("failed to create table");
+    database.execute("INSERT INTO map_test (name) VALUES ('a'), ('b'), ('c')").expect("failed to insert rows");
+
+    // Map rows with a closure
+    const SELECT_QUERY: &str = "SELECT id, name FROM map_test ORDER BY id ASC";
+    let ids: Vec<i64> = (database.query(SELECT_QUERY))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read(0)).collect())
+        .expect("failed to map rows");
+    assert_eq!(ids, [1, 2, 3]);
+
+    // Use the iterator adapters
+    let answer = database.query(SELECT_QUERY).and_then(|query| query.execute()).expect("failed to execute query");
+    let mut rows = answer.map_rows(|row| row.read::<String>(1)).skip(1);
+    assert_eq!(rows.next().transpose().expect("failed to map row"), Some("b".to_string()));
+    assert_eq!(rows.next().transpose().expect("failed to map row"), Some("c".to_string()));
+    assert!(rows.next().is_none());
+    assert!(rows.next().is_none());
+
+    // Collect rows into structs
+    let items = (database.query(SELECT_QUERY))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<Item>())
+        .expect("failed to collect rows");
+    assert_eq!(items[2], Item { id: 3, name: "c".to_string() });
+
+    // Propagate mapping errors
+    let error = (database.query(SELECT_QUERY))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<i64>(1)).collect::<Result<Vec<_>, _>>())
+        .expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Api);
+}
+
+#[test]
+fn read_ref() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database
+        .execute("CREATE TABLE ref_test (a INTEGER, b REAL, c TEXT, d BLOB, e BLOB)")
+        .expect("failed to create table");
+    database.execute("INSERT INTO ref_test VALUES (7, 4.4, 'Testolope', X'0102', X'')").expect("failed to insert row");
+    database
+        .execute("INSERT INTO ref_test VALUES (NULL, NULL, 'tESTOLOPE', NULL, NULL)")
+        .expect("failed to insert row");
+
+    // Borrow values from the rows
+    let mut answer = (database.query("SELECT * FROM ref_test ORDER BY rowid ASC"))
+        .and_then(|query| query.execute())
+        .expect("failed to execute query");
+    let row = answer.next_row().expect("failed to read row").expect("missing expected row");
+    assert_eq!(row.read_ref(0).expect("failed to read integer"), ValueRef::Integer(7));
+    assert_eq!(row.read_ref(1).expect("failed to read real"), ValueRef::Real(4.4));
+    assert_eq!(row.read_ref(2).expect("failed to read text"), ValueRef::Text("Testolope"));
+    assert_eq!(row.read_ref(3).expect("failed to read blob"), ValueRef::Blob(&[1, 2]));
+    assert_eq!(row.read_ref(4).expect("failed to read empty blob"), ValueRef::Blob(&[]));
+
+    // Advance to the next row
+    let row = answer.next_row().expect("failed to read row").expect("missing expected row");
+    assert_eq!(row.read_ref(0).expect("failed to read null"), ValueRef::Null);
+    assert_eq!(row.read_ref(2).expect("failed to read text"), ValueRef::Text("tESTOLOPE"));
+    assert_eq!(SqliteType::from(row.read_ref(2).expect("failed to read text")), SqliteType::Text("tESTOLOPE".into()));
+}
+
+#[test]
+fn bind_without_copy() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE bind_test (a INTEGER, b TEXT, c BLOB)").expect("failed to create table");
+
+    // Bind borrowed values and reuse the statement
+    let blob = vec![7u8; 1024 * 1024];
+    let text = String::from("Testolope");
+    let mut query =
+        database.query("INSERT INTO bind_test (a, b, c) VALUES (?, ?, ?)").expect("failed to prepare query");
+    for value in 0..4i64 {
+        let answer = (query.bind_ref(1, value))
+            .and_then(|query| query.bind_ref(2, text.as_str()))
+            .and_then(|query| query.bind_ref(3, blob.as_slice()))
+            .and_then(|query| query.execute())
+            .expect("failed to execute query");
+        query = answer.reset(true);
+    }
+    drop(query);
+
+    // Hand owned values over to SQLite
+    (database.query("INSERT INTO bind_test (a, b, c) VALUES (?, ?, ?)"))
+        .and_then(|query| query.bind_owned(1, 4))
+        .and_then(|query| query.bind_owned(2, String::from("tESTOLOPE")))
+        .and_then(|query| query.bind_owned(3, vec![4u8; 16]))
+        .and_then(|query| query.execute())
+        .expect("failed to execute query");
+    (database.query("INSERT INTO bind_test (a, b, c) VALUES (?, ?, ?)"))
+        .and_then(|query| query.bind_owned(1, 5))
+        .and_then(|query| query.bind_owned(2, String::new()))
+        .and_then(|query| query.bind_owned(3, Vec::<u8>::with_capacity(16)))
+        .and_then(|query| query.execute())
+        .expect("failed to execute query");
+
+    // Validate the values
+    let rows = (database.query("SELECT a, b, length(c) FROM bind_test ORDER BY a ASC"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| Ok((row.read::<String>(1)?, row.read::<i64>(2)?))).collect())
+        .expect("failed to read rows");
+    let rows: Vec<(String, i64)> = rows;
+    assert_eq!(rows.len(), 6);
+    assert_eq!(rows[0], ("Testolope".to_string(), 1024 * 1024));
+    assert_eq!(rows[4], ("tESTOLOPE".to_string(), 16));
+    assert_eq!(rows[5], (String::new(), 0));
+    let blob = (database.query("SELECT c FROM bind_test WHERE b = 'tESTOLOPE'"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<Vec<u8>>(0))
+        .expect("failed to read row");
+    assert_eq!(blob, [4; 16]);
+
+    // Binding to an invalid index must still free the handed over buffer
+    let query = database.query("SELECT ?").expect("failed to prepare query");
+    query.bind_owned(2, vec![7u8; 16]).expect_err("unexpected success");
+}
+
+#[test]
+fn tuples() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE tuple_test (a INTEGER, b TEXT, c REAL, d BLOB)").expect("failed to create table");
+
+    // Bind a tuple
+    (database.query("INSERT INTO tuple_test (a, b, c, d) VALUES (?, ?, ?, ?)"))
+        .and_then(|query| query.bind_all((1, "x", Some(2.5), None::<Vec<u8>>)))
+        .and_then(|query| query.execute())
+        .expect("failed to insert row");
+
+    // Read a tuple
+    let row = (database.query("SELECT a, b, c, d FROM tuple_test"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .expect("failed to read row");
+    let tuple = row.read_tuple::<(i64, String, Option<f64>, Option<Vec<u8>>)>().expect("failed to read tuple");
+    assert_eq!(tuple, (1, "x".to_string(), Some(2.5), None));
+
+    // Read a tuple with the wrong arity
+    let error = row.read_tuple::<(i64, String)>().expect_err("unexpected success");
+    assert_eq!(error.kind, ErrorKind::Api);
+
+    // Collect tuples and bind a large tuple
+    let values = (database.query("SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?"))
+        .and_then(|query| query.bind_all((0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15)))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<(u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8, u8)>())
+        .expect("failed to read tuples");
+    assert_eq!(values.len(), 1);
+    assert_eq!((values[0].0, values[0].15), (0, 15));
+}
+
+#[test]
+fn scalar_function() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+
+    // Register some functions
+    (database.create_scalar_function("rust_add", 2, ffi::SQLITE_DETERMINISTIC, |args| match args {
+        [ValueRef::Integer(a), ValueRef::Integer(b)] => Ok(SqliteType::Integer(a + b)),
+        [ValueRef::Text(a), ValueRef::Text(b)] => Ok(SqliteType::Text(format!("{a}{b}"))),
+        _ => Err(sqlite_tiny::err!("Unsupported arguments")),
+    }))
+    .expect("failed to create function");
+    (database.create_scalar_function("rust_panic", 0, ffi::SQLITE_DIRECTONLY, |_| panic!("Testolope")))
+        .expect("failed to create function");
+    let counter = std::sync::atomic::AtomicI64::new(0);
+    (database.create_scalar_function("rust_count", -1, ffi::SQLITE_INNOCUOUS, move |args| {
+        let count = counter.fetch_add(args.len() as i64, std::sync::atomic::Ordering::SeqCst);
+        Ok(SqliteType::Integer(count))
+    }))
+    .expect("failed to create function");
+
+    // Evaluates an SQL expression
+    let eval = |expression: &str| {
+        (database.query(&format!("SELECT {expression}")))
+            .and_then(|query| query.execute())
+            .and_then(|answer| answer.row())
+            .and_then(|row| row.read::<SqliteType>(0))
+    };
+
+    // Call the functions
+    assert_eq!(eval("rust_add(4, 3)").expect("failed to call function"), SqliteType::Integer(7));
+    assert_eq!(eval("rust_add('a', 'b')").expect("failed to call function"), SqliteType::Text("ab".to_string()));
+    assert_eq!(eval("rust_count(1, 2, 3)").expect("failed to call function"), SqliteType::Integer(0));
+    assert_eq!(eval("rust_count()").expect("failed to call function"), SqliteType::Integer(3));
+
+    // Propagate errors and panics
+    let error = eval("rust_add(4, 'b')").expect_err("unexpected success");
+    assert!(error.error.contains("Unsupported arguments"));
+    let error = eval("rust_panic()").expect_err("unexpected success");
+    assert!(error.error.contains("panicked"));
+
+    // Remove a function
+    database.remove_function("rust_add", 2).expect("failed to remove function");
+    eval("rust_add(4, 3)").expect_err("unexpected success");
+}
+
+/// A weighted mean aggregate that counts its live states
+struct WeightedMean {
+    /// The amount of live states
+    live: Arc<AtomicI64>,
+}
+/// The state of a weighted mean aggregate
+struct WeightedMeanState {
+    /// The sum of all weighted values and the sum of all weights
+    sums: (f64, f64),
+    /// The amount of live states
+    live: Arc<AtomicI64>,
+}
+impl Drop for WeightedMeanState {
+    fn drop(&mut self) {
+        self.live.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+impl Aggregate for WeightedMean {
+    type State = WeightedMeanState;
+
+    fn init(&self) -> Self::State {
+        self.live.fetch_add(1, Ordering::SeqCst);
+        WeightedMeanState { sums: (0.0, 0.0), live: self.live.clone() }
+    }
+    fn step(&self, state: &mut Self::State, args: &[ValueRef]) -> Result<(), Error> {
+        let (value, weight) = match args {
+            [_, ValueRef::Integer(13)] => panic!("Testolope"),
+            [ValueRef::Integer(value), ValueRef::Integer(weight)] => (*value as f64, *weight as f64),
+            _ => return Err(sqlite_tiny::err!("Unsupported arguments")),
+        };
+        state.sums.0 += value * weight;
+        state.sums.1 += weight;
+        Ok(())
+    }
+    fn finalize(&self, state: Self::State) -> Result<SqliteType, Error> {
+        match state.sums {
+            (_, 0.0) => Ok(SqliteType::Null),
+            (values, weights) => Ok(SqliteType::Real(values / weights)),
+        }
+    }
+}
+
+#[test]
+fn aggregate_function() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database
+        .execute("CREATE TABLE aggregate_test (grp TEXT, value INTEGER, weight INTEGER)")
+        .expect("failed to create table");
+    database
+        .execute("INSERT INTO aggregate_test VALUES ('a', 1, 1), ('a', 4, 2), ('b', 7, 1), ('c', 1, 13), ('d', 1, 'x')")
+        .expect("failed to insert rows");
+
+    // Register the aggregate
+    let live = Arc::new(AtomicI64::new(0));
+    (database.create_aggregate_function("wmean", 2, ffi::SQLITE_DETERMINISTIC, WeightedMean { live: live.clone() }))
+        .expect("failed to create aggregate");
+
+    // Compute the aggregate per group
+    let means = (database.query("SELECT grp, wmean(value, weight) FROM aggregate_test WHERE grp < 'c' GROUP BY grp"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<(String, f64)>())
+        .expect("failed to compute aggregate");
+    assert_eq!(means, [("a".to_string(), 3.0), ("b".to_string(), 7.0)]);
+
+    // Compute the aggregate over no rows
+    let mean = (database.query("SELECT wmean(value, weight) FROM aggregate_test WHERE grp = 'z'"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<Option<f64>>(0))
+        .expect("failed to compute aggregate");
+    assert_eq!(mean, None);
+
+    // Abort a query mid-way
+    let mut answer = (database
+        .query("SELECT grp, wmean(value, weight) FROM aggregate_test WHERE grp < 'c' GROUP BY grp"))
+    .and_then(|query| query.execute())
+    .expect("failed to execute query");
+    answer.next_row().expect("failed to read row").expect("missing expected row");
+    drop(answer);
+
+    // Propagate errors and panics
+    for group in ["c", "d"] {
+        (database.query("SELECT wmean(value, weight) FROM aggregate_test WHERE grp = ?"))
+            .and_then(|query| query.bind(1, group))
+            .and_then(|query| query.execute())
+            .expect_err("unexpected success");
+    }
+
+    // Ensure all states have been dropped
+    assert_eq!(live.load(Ordering::SeqCst), 0);
+}
+
+/// A moving geometric mean window function
+struct GeometricMean;
+impl Aggregate for GeometricMean {
+    /// The sum of all logarithms and the amount of values
+    type State = (f64, i64);
+
+    fn init(&self) -> Self::State {
+        (0.0, 0)
+    }
+    fn step(&self, state: &mut Self::State, args: &[ValueRef]) -> Result<(), Error> {
+        let [ValueRef::Integer(value)] = args else {
+            return Err(sqlite_tiny::err!("Unsupported arguments"));
+        };
+        state.0 += (*value as f64).ln();
+        state.1 += 1;
+        Ok(())
+    }
+    fn finalize(&self, state: Self::State) -> Result<SqliteType, Error> {
+        self.value(&state)
+    }
+}
+impl WindowFunction for GeometricMean {
+    fn inverse(&self, state: &mut Self::State, args: &[ValueRef]) -> Result<(), Error> {
+        let [ValueRef::Integer(value)] = args else {
+            return Err(sqlite_tiny::err!("Unsupported arguments"));
+        };
+        state.0 -= (*value as f64).ln();
+        state.1 -= 1;
+        Ok(())
+    }
+    fn value(&self, state: &Self::State) -> Result<SqliteType, Error> {
+        match state {
+            (_, 0) => Ok(SqliteType::Null),
+            (sum, count) => Ok(SqliteType::Real((sum / *count as f64).exp())),
+        }
+    }
+}
+
+#[test]
+fn window_function() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE window_test (x INTEGER, value INTEGER)").expect("failed to create table");
+    database.execute("INSERT INTO window_test VALUES (1, 2), (2, 8), (3, 4), (4, 1)").expect("failed to insert rows");
+    database.create_window_function("geomean", 1, 0, GeometricMean).expect("failed to create window function");
+
+    // Compute a moving geometric mean
+    const WINDOW_QUERY: &str = "
+        SELECT geomean(value) OVER (ORDER BY x ROWS BETWEEN 1 PRECEDING AND CURRENT ROW) FROM window_test ORDER BY x
+    ";
+    let means = (database.query(WINDOW_QUERY))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<f64>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to compute window function");
+    let expected = [2.0, 4.0, 32f64.sqrt(), 2.0];
+    assert!(means.iter().zip(expected).all(|(mean, expected)| (mean - expected).abs() < 1e-9), "{means:?}");
+
+    // Use the window function as plain aggregate
+    let mean = (database.query("SELECT geomean(value) FROM window_test WHERE x < 3"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<f64>(0))
+        .expect("failed to compute aggregate");
+    assert!((mean - 4.0).abs() < 1e-9);
+}
+
+/// Compares two strings so that embedded numbers are ordered by their value
+fn natural_cmp(a: &str, b: &str) -> cmp::Ordering {
+    /// Splits a string into its non-numeric prefix and numeric suffix
+    fn split(s: &str) -> (&str, u64) {
+        let index = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let (prefix, number) = s.split_at(index);
+        (prefix, number.parse().unwrap_or(0))
+    }
+    split(a).cmp(&split(b))
+}
+
+#[test]
+fn collation() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE collation_test (name TEXT)").expect("failed to create table");
+    database
+        .execute("INSERT INTO collation_test VALUES ('file10'), ('file2'), ('file1')")
+        .expect("failed to insert rows");
+    database.create_collation("natsort", natural_cmp).expect("failed to create collation");
+
+    // Sort with the collation
+    let names = (database.query("SELECT name FROM collation_test ORDER BY name COLLATE natsort"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<String>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to sort rows");
+    assert_eq!(names, ["file1", "file2", "file10"]);
+
+    // Unknown collations fail without a callback
+    let error =
+        database.query("SELECT name FROM collation_test ORDER BY name COLLATE reverse").expect_err("unknown collation");
+    assert_eq!(error.kind, ErrorKind::Error);
+
+    // Register collations lazily
+    database
+        .on_collation_needed(|name| match name {
+            "reverse" => Some(Box::new(|a: &str, b: &str| b.cmp(a)) as Collation),
+            _ => None,
+        })
+        .expect("failed to register callback");
+    let names = (database.query("SELECT name FROM collation_test ORDER BY name COLLATE reverse"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<String>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to sort rows");
+    assert_eq!(names, ["file2", "file10", "file1"]);
+
+    // Collations which are not provided by the callback still fail
+    let error =
+        database.query("SELECT name FROM collation_test ORDER BY name COLLATE unknown").expect_err("unknown collation");
+    assert_eq!(error.kind, ErrorKind::Error);
+    database.remove_collation_needed().expect("failed to remove callback");
+}
+
+/// An eponymous-only table-valued function `series(start, stop)` which yields all integers from `start` to `stop`
+struct Series;
+impl VirtualTable for Series {
+    type Aux = ();
+    type Cursor = SeriesCursor;
+
+    fn connect(_aux: &Self::Aux, _args: &[&str]) -> Result<(String, Self), Error> {
+        Ok(("CREATE TABLE x (value INTEGER, start HIDDEN, stop HIDDEN)".to_string(), Self))
+    }
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), Error> {
+        // Use the equality constraints on `start` and `stop` as arguments
+        let mut argv_index = 0;
+        for (index, constraint) in info.constraints().into_iter().enumerate() {
+            if constraint.usable && constraint.op == ConstraintOp::Eq && matches!(constraint.column, 1 | 2) {
+                info.use_constraint(index, constraint.column as u16, true)?;
+                argv_index += constraint.column;
+            }
+        }
+
+        // Only the plan with both arguments is efficient
+        info.set_idx_num(argv_index);
+        info.set_estimated_cost(if argv_index == 3 { 10.0 } else { 1e9 });
+        let order_by = info.order_by();
+        info.set_order_by_consumed(matches!(order_by.as_slice(), [order_by] if order_by.column == 0 && !order_by.desc));
+        Ok(())
+    }
+    fn open(&self) -> Result<Self::Cursor, Error> {
+        Ok(SeriesCursor { value: 0, stop: -1 })
+    }
+}
+
+/// A cursor over [`Series`]
+struct SeriesCursor {
+    value: i64,
+    stop: i64,
+}
+impl VTabCursor for SeriesCursor {
+    fn filter(&mut self, idx_num: i32, _idx_str: Option<&str>, args: &[ValueRef]) -> Result<(), Error> {
+        (self.value, self.stop) = match (idx_num, args) {
+            (3, [ValueRef::Integer(start), ValueRef::Integer(stop)]) => (*start, *stop),
+            _ => (0, -1),
+        };
+        Ok(())
+    }
+    fn next(&mut self) -> Result<(), Error> {
+        self.value += 1;
+        Ok(())
+    }
+    fn eof(&self) -> bool {
+        self.value > self.stop
+    }
+    fn column(&self, column: usize) -> Result<SqliteType, Error> {
+        match column {
+            0 => Ok(SqliteType::Integer(self.value)),
+            _ => Ok(SqliteType::Null),
+        }
+    }
+    fn rowid(&self) -> Result<i64, Error> {
+        Ok(self.value)
+    }
+}
+
+#[test]
+fn table_valued_function() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.create_eponymous_module::<Series>("series", ()).expect("failed to create module");
+
+    // Query the table-valued function
+    let values = (database.query("SELECT value FROM series(3, 7) ORDER BY value"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<i64>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to query table-valued function");
+    assert_eq!(values, [3, 4, 5, 6, 7]);
+
+    // Join the table-valued function
+    let sum = (database.query("SELECT sum(a.value * b.value) FROM series(1, 3) AS a, series(1, 2) AS b"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<i64>(0))
+        .expect("failed to join table-valued function");
+    assert_eq!(sum, 18);
+
+    // Eponymous-only modules cannot be used via `CREATE VIRTUAL TABLE`
+    let error = database.execute("CREATE VIRTUAL TABLE numbers USING series").expect_err("eponymous-only module");
+    assert_eq!(error.kind, ErrorKind::Error);
+
+    // Read-only modules cannot be modified
+    let error = database.execute("INSERT INTO series (value) VALUES (1)").expect_err("read-only module");
+    assert!(error.error.contains("may not be modified"), "{}", error.error);
+}
+
+/// A shared, in-process key-value map
+type Config = Arc<Mutex<BTreeMap<i64, String>>>;
+
+/// A writable virtual table which exposes a [`Config`] map
+struct ConfigTable {
+    config: Config,
+}
+impl VirtualTable for ConfigTable {
+    type Aux = Config;
+    type Cursor = ConfigCursor;
+    const WRITABLE: bool = true;
+
+    fn connect(aux: &Self::Aux, _args: &[&str]) -> Result<(String, Self), Error> {
+        Ok(("CREATE TABLE x (value TEXT)".to_string(), Self { config: aux.clone() }))
+    }
+    fn best_index(&self, info: &mut IndexInfo) -> Result<(), Error> {
+        // Look up single rows by rowid
+        let constraints = info.constraints().into_iter().enumerate();
+        for (index, constraint) in constraints {
+            if constraint.usable && constraint.op == ConstraintOp::Eq && constraint.column == -1 {
+                info.use_constraint(index, 1, true)?;
+                info.set_idx_str("rowid")?;
+                info.set_estimated_cost(1.0);
+                info.set_unique(true);
+                return Ok(());
+            }
+        }
+
+        // Scan the entire map
+        info.set_estimated_cost(1000.0);
+        Ok(())
+    }
+    fn open(&self) -> Result<Self::Cursor, Error> {
+        Ok(ConfigCursor { config: self.config.clone(), rows: Vec::new(), position: 0 })
+    }
+    fn update(&mut self, change: VTabChange) -> Result<i64, Error> {
+        let mut config = self.config.lock().expect("failed to lock config");
+        match change {
+            VTabChange::Delete { rowid } => {
+                config.remove(&rowid);
+                Ok(rowid)
+            }
+            VTabChange::Insert { rowid, values: [ValueRef::Text(value)] } => {
+                let rowid = rowid.unwrap_or_else(|| config.keys().next_back().map_or(1, |rowid| rowid + 1));
+                config.insert(rowid, value.to_string());
+                Ok(rowid)
+            }
+            VTabChange::Update { old_rowid, new_rowid, values: [ValueRef::Text(value)] } => {
+                config.remove(&old_rowid);
+                config.insert(new_rowid, value.to_string());
+                Ok(new_rowid)
+            }
+            _ => Err(Error::sqlite("Invalid config value".to_string(), ffi::SQLITE_CONSTRAINT, None)),
+        }
+    }
+    fn destroy(&mut self) -> Result<(), Error> {
+        self.config.lock().expect("failed to lock config").clear();
+        Ok(())
+    }
+}
+
+/// A cursor over [`ConfigTable`]
+struct ConfigCursor {
+    config: Config,
+    rows: Vec<(i64, String)>,
+    position: usize,
+}
+impl VTabCursor for ConfigCursor {
+    fn filter(&mut self, _idx_num: i32, idx_str: Option<&str>, args: &[ValueRef]) -> Result<(), Error> {
+        // Take a snapshot of the matching rows
+        let config = self.config.lock().expect("failed to lock config");
+        self.rows = match (idx_str, args) {
+            (Some("rowid"), [ValueRef::Integer(rowid)]) => {
+                config.get_key_value(rowid).map(|(rowid, value)| (*rowid, value.clone())).into_iter().collect()
+            }
+            (Some("rowid"), _) => Vec::new(),
+            _ => config.iter().map(|(rowid, value)| (*rowid, value.clone())).collect(),
+        };
+        self.position = 0;
+        Ok(())
+    }
+    fn next(&mut self) -> Result<(), Error> {
+        self.position += 1;
+        Ok(())
+    }
+    fn eof(&self) -> bool {
+        self.position >= self.rows.len()
+    }
+    fn column(&self, _column: usize) -> Result<SqliteType, Error> {
+        let (_, value) = &self.rows[self.position];
+        Ok(SqliteType::Text(value.clone()))
+    }
+    fn rowid(&self) -> Result<i64, Error> {
+        let (rowid, _) = &self.rows[self.position];
+        Ok(*rowid)
+    }
+}
+
+#[test]
+fn virtual_table() {
+    // Create in-memory database and expose the config map
+    let config = Config::default();
+    config.lock().expect("failed to lock config").insert(7, "seven".to_string());
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.create_module::<ConfigTable>("config", config.clone()).expect("failed to create module");
+    database.execute("CREATE VIRTUAL TABLE settings USING config").expect("failed to create virtual table");
+
+    // Modify the map via SQL
+    database.execute("INSERT INTO settings (value) VALUES ('eight'), ('nine')").expect("failed to insert rows");
+    database.execute("INSERT INTO settings (rowid, value) VALUES (1, 'one')").expect("failed to insert row");
+    database.execute("UPDATE settings SET value = 'EIGHT' WHERE rowid = 8").expect("failed to update row");
+    database.execute("DELETE FROM settings WHERE value = 'nine'").expect("failed to delete row");
+    let expected = BTreeMap::from([(1, "one".to_string()), (7, "seven".to_string()), (8, "EIGHT".to_string())]);
+    assert_eq!(*config.lock().expect("failed to lock config"), expected);
+
+    // Read the map via SQL
+    let rows = (database.query("SELECT rowid, value FROM settings"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read_tuple::<(i64, String)>()).collect::<Result<Vec<_>, _>>())
+        .expect("failed to read rows");
+    assert_eq!(rows, [(1, "one".to_string()), (7, "seven".to_string()), (8, "EIGHT".to_string())]);
+
+    // Look up a single row
+    let value = (database.query("SELECT value FROM settings WHERE rowid = ?"))
+        .and_then(|query| query.bind(1, 7))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<String>(0))
+        .expect("failed to look up row");
+    assert_eq!(value, "seven");
+
+    // Errors are propagated
+    let error = database.execute("INSERT INTO settings (value) VALUES (42)").expect_err("invalid config value");
+    assert_eq!(error.kind, ErrorKind::Constraint);
+    assert!(error.error.contains("Invalid config value"), "{}", error.error);
+
+    // Dropping the table destroys the map, while closing the database only disconnects from it
+    database.execute("CREATE VIRTUAL TABLE other_settings USING config").expect("failed to create virtual table");
+    database.execute("DROP TABLE settings").expect("failed to drop virtual table");
+    assert!(config.lock().expect("failed to lock config").is_empty());
+    config.lock().expect("failed to lock config").insert(2, "two".to_string());
+    drop(database);
+    assert_eq!(config.lock().expect("failed to lock config").len(), 1);
+}
+
+#[test]
+fn bind_array() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE array_test (id INTEGER, name TEXT)").expect("failed to create table");
+    database
+        .execute("INSERT INTO array_test VALUES (1, 'one'), (2, 'two'), (3, 'three'), (4, 'four')")
+        .expect("failed to insert rows");
+
+    // Select by integers
+    let ids: &[i64] = &[4, 2, 7];
+    let names = (database.query("SELECT name FROM array_test WHERE id IN carray(?1) ORDER BY id"))
+        .and_then(|query| query.bind_array(1, ids))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<String>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to select by integers");
+    assert_eq!(names, ["two", "four"]);
+
+    // Select by strings
+    let names = vec!["three".to_string(), "one".to_string()];
+    let ids = (database.query("SELECT id FROM array_test WHERE name IN carray(?1) ORDER BY id"))
+        .and_then(|query| query.bind_array(1, &names))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<i64>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to select by strings");
+    assert_eq!(ids, [1, 3]);
+
+    // Read reals and blobs back
+    let reals: &[f64] = &[0.5, 1.5];
+    let sum = (database.query("SELECT sum(value) FROM carray(?1)"))
+        .and_then(|query| query.bind_array(1, reals))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<f64>(0))
+        .expect("failed to sum reals");
+    assert_eq!(sum, 2.0);
+    let blobs = vec![b"\x00\x01".to_vec(), Vec::new(), b"abc".to_vec()];
+    let lengths = (database.query("SELECT length(value) FROM carray(?1)"))
+        .and_then(|query| query.bind_array(1, &blobs))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.map_rows(|row| row.read::<i64>(0)).collect::<Result<Vec<_>, _>>())
+        .expect("failed to read blobs");
+    assert_eq!(lengths, [2, 0, 3]);
+
+    // Bind an empty array
+    let empty: &[i64] = &[];
+    let count = (database.query("SELECT count(*) FROM array_test WHERE id IN carray(?1)"))
+        .and_then(|query| query.bind_array(1, empty))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<i64>(0))
+        .expect("failed to select by empty array");
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn backup() {
+    // Create a source database with enough rows to span multiple pages
+    let source = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    source.execute("CREATE TABLE backup_test (id INTEGER PRIMARY KEY, data BLOB)").expect("failed to create table");
+    source
+        .execute(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 64)
+         INSERT INTO backup_test (data) SELECT randomblob(1024) FROM n",
+        )
+        .expect("failed to insert rows");
+
+    // Back up into another database and record the progress
+    let destination = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    let mut steps = Vec::new();
+    source.backup_to(&destination, 8, |progress| steps.push(progress)).expect("failed to back up database");
+    assert!(steps.len() > 1, "{steps:?}");
+    assert_eq!(steps.last().map(|progress| progress.remaining), Some(0));
+    let count_rows = |sqlite: &Sqlite| {
+        (sqlite.query("SELECT count(*) FROM backup_test"))
+            .and_then(|query| query.execute())
+            .and_then(|answer| answer.row())
+            .and_then(|row| row.read::<i64>(0))
+            .expect("failed to count rows")
+    };
+    assert_eq!(count_rows(&destination), 64);
+
+    // Step a backup manually
+    let copy = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    let mut backup = source.backup(&copy).expect("failed to start backup").with_retry_policy(RetryPolicy::never());
+    assert!(!backup.step(1).expect("failed to perform backup step"));
+    let Progress { remaining, page_count } = backup.progress();
+    assert_eq!(remaining + 1, page_count);
+    while !backup.step(4).expect("failed to perform backup step") {}
+    backup.finish().expect("failed to finish backup");
+    assert_eq!(count_rows(&copy), 64);
+
+    // Restore a modified database from the backup
+    source.execute("DELETE FROM backup_test").expect("failed to delete rows");
+    assert_eq!(count_rows(&source), 0);
+    source.restore_from(&destination, -1, |_| ()).expect("failed to restore database");
+    assert_eq!(count_rows(&source), 64);
+
+    // A database cannot be backed up into itself
+    source.backup_to(&source, -1, |_| ()).expect_err("backup into itself");
+
+    // Restoring from a nonexistent path fails without creating the file or touching the data
+    let missing = std::env::temp_dir().join(format!("sqlite-tiny-missing-{}.db", std::process::id()));
+    let missing_path = missing.to_str().expect("invalid temp path");
+    source.restore_from(missing_path, -1, |_| ()).expect_err("restored from nonexistent database");
+    assert!(!missing.exists());
+    assert_eq!(count_rows(&source), 64);
+}
+
+#[test]
+fn serialize() {
+    // Create a database and serialize it
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE serialize_test (value TEXT)").expect("failed to create table");
+    database.execute("INSERT INTO serialize_test VALUES ('snapshot')").expect("failed to insert row");
+    let bytes = database.serialize("main").expect("failed to serialize database");
+    assert!(bytes.starts_with(b"SQLite format 3\0"));
+    database.serialize("unknown").expect_err("unknown schema");
+
+    // Deserialize the database into a writable connection
+    let copy = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    copy.deserialize("main", &bytes, false).expect("failed to deserialize database");
+    copy.execute("INSERT INTO serialize_test VALUES (randomblob(65536))").expect("failed to grow database");
+    let count = (copy.query("SELECT count(*) FROM serialize_test"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<i64>(0))
+        .expect("failed to count rows");
+    assert_eq!(count, 2);
+
+    // Open the database read-only from bytes
+    let snapshot = Sqlite::from_bytes(&bytes).expect("failed to open serialized database");
+    let value = (snapshot.query("SELECT value FROM serialize_test"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<String>(0))
+        .expect("failed to read row");
+    assert_eq!(value, "snapshot");
+    let error = snapshot.execute("INSERT INTO serialize_test VALUES ('write')").expect_err("read-only database");
+    assert_eq!(error.kind, ErrorKind::ReadOnly);
+}
+
+#[test]
+fn blob_io() {
+    // Create in-memory database and preallocate a BLOB
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE blob_test (id INTEGER PRIMARY KEY, data BLOB)").expect("failed to create table");
+    (database.query("INSERT INTO blob_test (id, data) VALUES (1, ?1), (2, x'0102')"))
+        .and_then(|query| query.bind_zeroblob(1, 100_000))
+        .and_then(|query| query.execute())
+        .expect("failed to insert rows");
+
+    // Stream data into the BLOB
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let mut blob = database.open_blob("main", "blob_test", "data", 1, true).expect("failed to open blob");
+    assert_eq!(blob.len(), 100_000);
+    io::copy(&mut data.as_slice(), &mut blob).expect("failed to write blob");
+    blob.write_all(b"overflow").expect_err("write beyond the end of the blob");
+
+    // Stream the BLOB back and seek within it
+    blob.rewind().expect("failed to rewind blob");
+    let mut copy = Vec::new();
+    io::copy(&mut blob, &mut copy).expect("failed to read blob");
+    assert_eq!(copy, data);
+    blob.seek(SeekFrom::End(-3)).expect("failed to seek blob");
+    let mut tail = Vec::new();
+    blob.read_to_end(&mut tail).expect("failed to read blob");
+    assert_eq!(tail, data[data.len() - 3..]);
+    blob.seek(SeekFrom::Current(-100_001)).expect_err("seek before the start of the blob");
+
+    // Seeking past the end is allowed, and reads and writes hit EOF
+    blob.seek(SeekFrom::End(1)).expect("failed to seek past the end of the blob");
+    let mut buf = [0; 16];
+    assert_eq!(blob.read(&mut buf).expect("failed to read past the end of the blob"), 0);
+    assert_eq!(blob.write(&buf).expect("failed to write past the end of the blob"), 0);
+
+    // Reopen the handle on another row
+    blob.reopen(2).expect("failed to reopen blob");
+    let mut bytes = Vec::new();
+    blob.read_to_end(&mut bytes).expect("failed to read blob");
+    assert_eq!(bytes, [1, 2]);
+    drop(blob);
+
+    // Read-only handles cannot be written
+    let mut blob = database.open_blob("main", "blob_test", "data", 2, false).expect("failed to open blob");
+    blob.write_all(&[3]).expect_err("write to read-only blob");
+    database.open_blob("main", "blob_test", "data", 3, false).expect_err("unknown row");
+}
+
+#[test]
+fn hooks() {
+    // Create in-memory database and register the hooks
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE hook_test (value TEXT)").expect("failed to create table");
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let (veto, rollbacks) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicI64::new(0)));
+    database.on_update({
+        let changes = changes.clone();
+        move |op, schema, table, rowid| {
+            let mut changes = changes.lock().expect("failed to lock changes");
+            changes.push((op, format!("{schema}.{table}"), rowid));
+        }
+    });
+    database.on_commit({
+        let veto = veto.clone();
+        move || veto.load(Ordering::SeqCst)
+    });
+    database.on_rollback({
+        let rollbacks = rollbacks.clone();
+        move || {
+            rollbacks.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    // Record changes
+    database.execute("INSERT INTO hook_test VALUES ('a'), ('b')").expect("failed to insert rows");
+    database.execute("UPDATE hook_test SET value = 'c' WHERE rowid = 2").expect("failed to update row");
+    let expected = [
+        (UpdateOp::Insert, "main.hook_test".to_string(), 1),
+        (UpdateOp::Insert, "main.hook_test".to_string(), 2),
+        (UpdateOp::Update, "main.hook_test".to_string(), 2),
+    ];
+    assert_eq!(*changes.lock().expect("failed to lock changes"), expected);
+    assert_eq!(rollbacks.load(Ordering::SeqCst), 0);
+
+    // Veto a commit, which rolls back the transaction
+    veto.store(true, Ordering::SeqCst);
+    let error = database.execute("DELETE FROM hook_test").expect_err("commit was not vetoed");
+    assert_eq!(error.kind, ErrorKind::Constraint);
+    assert_eq!(rollbacks.load(Ordering::SeqCst), 1);
+    let count = (database.query("SELECT count(*) FROM hook_test"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .and_then(|row| row.read::<i64>(0))
+        .expect("failed to count rows");
+    assert_eq!(count, 2);
+
+    // Panicking callbacks are caught, and a panicking commit callback vetoes the commit
+    database.on_update(|_, _, _, _| panic!("update hook panicked"));
+    database.on_commit(|| panic!("commit hook panicked"));
+    let error = database.execute("DELETE FROM hook_test").expect_err("commit was not vetoed");
+    assert_eq!(error.kind, ErrorKind::Constraint);
+    assert_eq!(rollbacks.load(Ordering::SeqCst), 2);
+
+    // Replacing and removing the hooks frees the closures
+    assert_eq!((Arc::strong_count(&changes), Arc::strong_count(&veto)), (1, 1));
+    database.remove_update_hook();
+    database.remove_commit_hook();
+    database.remove_rollback_hook();
+    assert_eq!(Arc::strong_count(&rollbacks), 1);
+    database.execute("DELETE FROM hook_test").expect("failed to delete rows");
+    assert_eq!(rollbacks.load(Ordering::SeqCst), 2);
+
+    // Callbacks only need to be `Send`, so they can keep non-`Sync` state
+    let (sender, receiver) = mpsc::channel();
+    let counter = Cell::new(0);
+    database.on_update(move |_, _, _, _| {
+        counter.set(counter.get() + 1);
+        let _ = sender.send(counter.get());
+    });
+    database.execute("INSERT INTO hook_test VALUES ('d'), ('e')").expect("failed to insert rows");
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [1, 2]);
+    database.remove_update_hook();
+
+    // Dropping the database frees the closures
+    database.on_rollback({
+        let rollbacks = rollbacks.clone();
+        move || {
+            rollbacks.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    assert_eq!(Arc::strong_count(&rollbacks), 2);
+    drop(database);
+    assert_eq!(Arc::strong_count(&rollbacks), 1);
+}
+
+#[test]
+#[cfg(feature = "preupdate-hook")]
+fn preupdate_hook() {
+    use sqlite_tiny::api::hooks::PreUpdate;
+
+    /// A recorded change with the old and new values of the first column, the trigger depth and the BLOB write column
+    type Change = (UpdateOp, Option<SqliteType>, Option<SqliteType>, i32, Option<i32>);
+
+    // Create in-memory database and register the hook
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE audit_test (value, data BLOB)").expect("failed to create table");
+    database.execute("CREATE TABLE audit_log (value)").expect("failed to create table");
+    database
+        .execute("CREATE TRIGGER audit AFTER DELETE ON audit_test BEGIN INSERT INTO audit_log VALUES (old.value); END")
+        .expect("failed to create trigger");
+    let changes: Arc<Mutex<Vec<Change>>> = Arc::default();
+    database.on_preupdate({
+        let changes = changes.clone();
+        move |preupdate: &PreUpdate| {
+            // Validate the accessor
+            assert_eq!(preupdate.database(), "main");
+            assert_eq!(preupdate.count(), if preupdate.table() == "audit_test" { 2 } else { 1 });
+            assert!(preupdate.old(2).is_err() && preupdate.new(-1).is_err());
+
+            // Record the change
+            let (old, new) = (preupdate.old(0).ok(), preupdate.new(0).ok());
+            let change = (preupdate.op(), old, new, preupdate.depth(), preupdate.blobwrite());
+            changes.lock().expect("failed to lock changes").push(change);
+        }
+    });
+
+    // Perform some changes
+    database.execute("INSERT INTO audit_test VALUES ('a', zeroblob(4))").expect("failed to insert row");
+    database.execute("UPDATE audit_test SET value = 7").expect("failed to update row");
+    let mut blob = database.open_blob("main", "audit_test", "data", 1, true).expect("failed to open blob");
+    blob.write_all(b"test").expect("failed to write blob");
+    drop(blob);
+    database.execute("DELETE FROM audit_test").expect("failed to delete row");
+
+    // Validate the recorded changes
+    let (text, integer) = (SqliteType::Text("a".to_string()), SqliteType::Integer(7));
+    let expected = [
+        (UpdateOp::Insert, None, Some(text.clone()), 0, None),
+        (UpdateOp::Update, Some(text), Some(integer.clone()), 0, None),
+        (UpdateOp::Delete, Some(integer.clone()), None, 0, Some(1)),
+        (UpdateOp::Delete, Some(integer.clone()), None, 0, None),
+        (UpdateOp::Insert, None, Some(integer), 1, None),
+    ];
+    assert_eq!(*changes.lock().expect("failed to lock changes"), expected);
+
+    // Remove the hook
+    database.remove_preupdate_hook();
+    assert_eq!(Arc::strong_count(&changes), 1);
+}
+
+#[test]
+#[cfg(feature = "session")]
+fn session() {
+    use sqlite_tiny::api::session::{self, Change, ConflictAction, ConflictKind};
+
+    /// A schema for the synchronized table
+    const CREATE_SYNC_TABLE: &str = "CREATE TABLE sync_test (id INTEGER PRIMARY KEY, value TEXT)";
+
+    // Create the source and the replica
+    let source = Sqlite::uri("file:session_source.db?mode=memory").expect("failed to open database");
+    let replica = Sqlite::uri("file:session_replica.db?mode=memory").expect("failed to open database");
+    for database in [&source, &replica] {
+        database.execute(CREATE_SYNC_TABLE).expect("failed to create table");
+        database.execute("INSERT INTO sync_test VALUES (1, 'a'), (2, 'b')").expect("failed to insert rows");
+    }
+
+    // Record some changes
+    let mut recorder = source.session("main").expect("failed to create session");
+    recorder.attach("sync_test").expect("failed to attach table");
+    assert!(recorder.is_empty());
+    source.execute("INSERT INTO sync_test VALUES (3, 'c')").expect("failed to insert row");
+    source.execute("UPDATE sync_test SET value = 'B' WHERE id = 2").expect("failed to update row");
+    source.execute("DELETE FROM sync_test WHERE id = 1").expect("failed to delete row");
+    assert!(!recorder.is_empty());
+    let changeset = recorder.changeset().expect("failed to create changeset");
+    let patchset = recorder.patchset().expect("failed to create patchset");
+    drop(recorder);
+
+    // Iterate over the changes
+    let text = |value: &str| Some(SqliteType::Text(value.to_string()));
+    let mut changes: Vec<Change> =
+        (session::iter(&changeset)).and_then(|changes| changes.collect()).expect("failed to iterate over changeset");
+    changes.sort_by_key(|change| change.op as u8);
+    let ops: Vec<_> = changes.iter().map(|change| change.op).collect();
+    assert_eq!(ops, [UpdateOp::Insert, UpdateOp::Update, UpdateOp::Delete]);
+    assert!(changes.iter().all(|change| change.table == "sync_test" && !change.indirect));
+    assert!(changes.iter().all(|change| change.primary_key == [true, false]));
+    assert_eq!(changes[1].old, [Some(SqliteType::Integer(2)), text("b")]);
+    assert_eq!(changes[1].new, [None, text("B")]);
+    assert_eq!(changes[2].new, []);
+
+    // Apply the changeset to the replica
+    replica.apply_changeset(&changeset, |_| ConflictAction::Abort).expect("failed to apply changeset");
+    let dump = |database: &Sqlite| -> Vec<(i64, String)> {
+        (database.query("SELECT id, value FROM sync_test ORDER BY id"))
+            .and_then(|query| query.execute())
+            .and_then(|answer| answer.collect_rows())
+            .expect("failed to dump table")
+    };
+    assert_eq!(dump(&replica), dump(&source));
+
+    // Applying the changeset again conflicts
+    let mut conflicts = Vec::new();
+    let result = replica.apply_changeset(&changeset, |conflict| {
+        conflicts.push(conflict.kind);
+        match conflict.kind {
+            ConflictKind::Conflict => assert_eq!(conflict.current, [Some(SqliteType::Integer(3)), text("c")]),
+            ConflictKind::Data => assert_eq!(conflict.current, [Some(SqliteType::Integer(2)), text("B")]),
+            _ => assert!(conflict.current.is_empty()),
+        }
+        ConflictAction::Omit
+    });
+    result.expect("failed to apply changeset");
+    conflicts.sort_by_key(|kind| *kind as u8);
+    assert_eq!(conflicts, [ConflictKind::Data, ConflictKind::NotFound, ConflictKind::Conflict]);
+
+    // Aborting rolls back all changes, and panicking handlers abort
+    replica.execute("UPDATE sync_test SET value = 'x'").expect("failed to update rows");
+    replica.apply_changeset(&changeset, |_| ConflictAction::Abort).expect_err("changeset was applied");
+    replica.apply_changeset(&changeset, |_| panic!("conflict handler panicked")).expect_err("changeset was applied");
+    assert_eq!(dump(&replica), [(2, "x".to_string()), (3, "x".to_string())]);
+
+    // Revert the changes via the inverted changeset
+    let inverted = session::invert(&changeset).expect("failed to invert changeset");
+    session::invert(&patchset).expect_err("patchset was inverted");
+    source.apply_changeset(&inverted, |_| ConflictAction::Abort).expect("failed to apply changeset");
+    assert_eq!(dump(&source), [(1, "a".to_string()), (2, "b".to_string())]);
+
+    // A changeset concatenated with its inverse has no effect
+    let noop = session::concat(&changeset, &inverted).expect("failed to concatenate changesets");
+    assert_eq!(session::iter(&noop).expect("failed to iterate over changeset").count(), 0);
+
+    // Apply the patchset
+    source.apply_changeset(&patchset, |_| ConflictAction::Abort).expect("failed to apply patchset");
+    assert_eq!(dump(&source), [(2, "B".to_string()), (3, "c".to_string())]);
+}
+
+#[test]
+fn trace() {
+    // Create in-memory database and record all trace events
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute("CREATE TABLE trace_test (value INTEGER)").expect("failed to create table");
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let result = database.trace(TraceMask::ALL, {
+        let events = events.clone();
+        move |event| {
+            let event = match event {
+                TraceEvent::Stmt { sql } => format!("stmt: {sql}"),
+                TraceEvent::Profile { sql, .. } => format!("profile: {sql}"),
+                TraceEvent::Row { sql } => format!("row: {sql}"),
+                TraceEvent::Close => "close".to_string(),
+            };
+            events.lock().expect("failed to lock events").push(event);
+        }
+    });
+    result.expect("failed to register trace callback");
+
+    // Run some statements
+    (database.query("INSERT INTO trace_test VALUES (?1)"))
+        .and_then(|query| query.bind(1, 7))
+        .and_then(|query| query.execute())
+        .expect("failed to insert row");
+    (database.query("SELECT value FROM trace_test"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.row())
+        .expect("failed to select row");
+    let expected = [
+        "stmt: INSERT INTO trace_test VALUES (7)",
+        "profile: INSERT INTO trace_test VALUES (?1)",
+        "stmt: SELECT value FROM trace_test",
+        "row: SELECT value FROM trace_test",
+    ];
+    assert_eq!(events.lock().expect("failed to lock events").get(..4).unwrap_or_default(), expected);
+
+    // Filter events and observe the close event
+    database.trace(TraceMask::CLOSE, |_| ()).expect("failed to replace trace callback");
+    assert_eq!(Arc::strong_count(&events), 1);
+    database.remove_trace().expect("failed to remove trace callback");
+    database
+        .trace(TraceMask::STMT | TraceMask::CLOSE, {
+            let events = events.clone();
+            move |event| events.lock().expect("failed to lock events").push(format!("{event:?}"))
+        })
+        .expect("failed to register trace callback");
+    events.lock().expect("failed to lock events").clear();
+    database.execute("DELETE FROM trace_test").expect("failed to delete rows");
+    drop(database);
+    let expected = [r#"Stmt { sql: "DELETE FROM trace_test" }"#, "Close"];
+    assert_eq!(*events.lock().expect("failed to lock events"), expected);
+    assert_eq!(Arc::strong_count(&events), 1);
+}
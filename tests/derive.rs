@@ -0,0 +1,141 @@
+#![cfg(feature = "derive")]
+
+use sqlite_tiny::{FromRow, Sqlite, ToParams};
+
+/// A schema for a basic test table
+const CREATE_TABLE: &str = "
+    CREATE TABLE users (
+        id INTEGER PRIMARY KEY NOT NULL,
+        user_name TEXT NOT NULL,
+        email TEXT,
+        type TEXT NOT NULL
+    ) STRICT;
+";
+
+/// A struct with named fields
+#[derive(Debug, Clone, PartialEq, FromRow, ToParams)]
+struct User {
+    id: i64,
+    #[sqlite(rename = "user_name")]
+    name: String,
+    email: Option<String>,
+    r#type: String,
+    #[sqlite(skip)]
+    cached: Vec<u8>,
+    #[sqlite(default)]
+    score: Option<i64>,
+}
+
+/// A tuple struct
+#[derive(Debug, PartialEq, FromRow, ToParams)]
+struct Pair(i64, String);
+
+/// A generic struct with explicit indices
+#[derive(Debug, PartialEq, FromRow, ToParams)]
+struct Generic<'a>
+where
+    Self: Sized,
+{
+    #[sqlite(index = 1)]
+    value: String,
+    #[sqlite(skip)]
+    marker: std::marker::PhantomData<&'a ()>,
+}
+
+/// A generic struct with default parameters and bounds
+#[derive(Debug, PartialEq, FromRow, ToParams)]
+struct Entry<T = i64, const N: usize = 1>
+where
+    T: PartialEq,
+{
+    id: T,
+    #[sqlite(rename = "user_name")]
+    name: Option<String>,
+    #[sqlite(skip)]
+    cached: [Option<T>; N],
+}
+
+#[test]
+fn named() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute(CREATE_TABLE).expect("failed to create table");
+
+    // Insert users
+    let user = User {
+        id: 7,
+        name: "Testolope".to_string(),
+        email: None,
+        r#type: "admin".to_string(),
+        cached: b"cached".to_vec(),
+        score: Some(4),
+    };
+    const INSERT_QUERY: &str = "INSERT INTO users (id, user_name, email, type) VALUES (:id, @user_name, $email, :type)";
+    (database.query(INSERT_QUERY))
+        .and_then(|query| query.bind_params(&user))
+        .and_then(|query| query.execute())
+        .expect("failed to insert user");
+
+    // Binding to a statement without a parameter for `email` must fail
+    const INCOMPLETE_QUERY: &str = "INSERT INTO users (id, user_name, type) VALUES (:id, :user_name, :type)";
+    let incomplete = (database.query(INCOMPLETE_QUERY)).and_then(|query| query.bind_params(&user));
+    assert!(incomplete.is_err(), "binding to a statement with a missing parameter should fail");
+
+    // Read users back
+    let users = (database.query("SELECT * FROM users"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<User>())
+        .expect("failed to read users");
+    assert_eq!(users, [User { cached: Vec::new(), score: None, ..user }]);
+}
+
+#[test]
+fn positional() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute(CREATE_TABLE).expect("failed to create table");
+
+    // Insert a user
+    let pair = Pair(7, "Testolope".to_string());
+    (database.query("INSERT INTO users (id, user_name, type) VALUES (?, ?, 'user')"))
+        .and_then(|query| query.bind_params(&pair))
+        .and_then(|query| query.execute())
+        .expect("failed to insert user");
+
+    // Read the user back
+    let pairs = (database.query("SELECT id, user_name FROM users"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<Pair>())
+        .expect("failed to read users");
+    assert_eq!(pairs, [pair]);
+
+    // Read the name via a generic struct
+    let generic = Generic { value: "Testolope".to_string(), marker: std::marker::PhantomData };
+    let names = (database.query("SELECT id, user_name FROM users WHERE ?1 IS NULL AND user_name = ?2"))
+        .and_then(|query| query.bind_params(&generic))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<Generic>())
+        .expect("failed to read users");
+    assert_eq!(names, [generic]);
+}
+
+#[test]
+fn generic() {
+    // Create in-memory database
+    let database = Sqlite::uri("file:test.db?mode=memory").expect("failed to open database");
+    database.execute(CREATE_TABLE).expect("failed to create table");
+
+    // Insert a user via the default type parameter
+    let entry: Entry = Entry { id: 7, name: Some("Testolope".to_string()), cached: [Some(4)] };
+    (database.query("INSERT INTO users (id, user_name, type) VALUES (:id, :user_name, 'user')"))
+        .and_then(|query| query.bind_params(&entry))
+        .and_then(|query| query.execute())
+        .expect("failed to insert user");
+
+    // Read the user back with another type parameter
+    let entries = (database.query("SELECT CAST(id AS TEXT) AS id, user_name FROM users"))
+        .and_then(|query| query.execute())
+        .and_then(|answer| answer.collect_rows::<Entry<String, 2>>())
+        .expect("failed to read users");
+    assert_eq!(entries, [Entry { id: "7".to_string(), name: entry.name, cached: [None, None] }]);
+}